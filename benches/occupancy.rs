@@ -0,0 +1,110 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ratatui::style::Color;
+use specs::{Builder, Join, ReadStorage, RunNow, World, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrafficLight, TrainColors, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::track_graph::TrackGraph;
+use terminal_transport_game::track_graph_system::UpdatingTrackGraph;
+use terminal_transport_game::traffic_light_system::ActiveTrafficLight;
+use terminal_transport_game::train::spawn_train;
+
+const TRAIN_COUNTS: [usize; 3] = [10, 100, 500];
+/// Tiles of track between two signals.
+const SIGNAL_SPACING: usize = 8;
+
+/// Tiles of a ring in the order a train runs along them.
+fn ring_tiles(map: &Map) -> Vec<Point> {
+    let start = Point::new(0, 1);
+    let mut tiles = vec![start];
+    let (mut pos, mut dir) = (start, Direction::Up);
+    loop {
+        dir = map.get_next_direction(pos, dir);
+        pos += Point::from(dir);
+        if pos == start {
+            return tiles;
+        }
+        tiles.push(pos);
+    }
+}
+
+/// A ring long enough for `trains` two-car trains, with signals all along it.
+fn world_with_trains(trains: usize) -> App {
+    let mut app = App::new();
+    let side = (trains * 2) as i32 + 2;
+    let map = Map::simple_ring(side, side);
+    let tiles = ring_tiles(&map);
+    app.ecs.insert(map);
+
+    for i in 0..trains {
+        let head = tiles[i * 4 + 1];
+        let parts = TrainParts::new(
+            &[head, tiles[i * 4]],
+            &[
+                Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+                Vehicle::passenger_car(1.5),
+            ],
+        );
+        let direction = Direction::between(tiles[i * 4], head).expect("Ring tiles are adjacent");
+        spawn_train(
+            &mut app.ecs,
+            parts,
+            direction,
+            TrainColors {
+                main_color: Color::Blue,
+                head_color: Color::LightBlue,
+            },
+        );
+    }
+
+    for pair in tiles.windows(2).step_by(SIGNAL_SPACING) {
+        let direction = Direction::between(pair[0], pair[1]).expect("Ring tiles are adjacent");
+        app.ecs
+            .create_entity()
+            .with(TrafficLight { is_green: true })
+            .with(pair[0])
+            .with(direction)
+            .build();
+    }
+
+    UpdatingTrackGraph {}.run_now(&app.ecs);
+    app
+}
+
+/// How the signals were set before the occupancy grid: every block tile
+/// checked against a list of all the train positions.
+fn linear_scan(ecs: &World) {
+    let graph = ecs.fetch::<TrackGraph>();
+    let trains: ReadStorage<TrainParts> = ecs.read_storage();
+    let positions: ReadStorage<Point> = ecs.read_storage();
+    let directions: ReadStorage<Direction> = ecs.read_storage();
+    let mut lights = ecs.write_storage::<TrafficLight>();
+
+    let train_positions: Vec<Point> = trains.join().flat_map(|t| t.positions()).collect();
+    for (p, d, light) in (&positions, &directions, &mut lights).join() {
+        let block = graph.block_from(*p, *d);
+        light.is_green = !block.iter().any(|p| train_positions.contains(p));
+    }
+}
+
+fn signals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signals");
+    for trains in TRAIN_COUNTS {
+        let app = world_with_trains(trains);
+        group.bench_with_input(
+            BenchmarkId::new("occupancy_grid", trains),
+            &app,
+            |b, app| b.iter(|| ActiveTrafficLight {}.run_now(&app.ecs)),
+        );
+        group.bench_with_input(BenchmarkId::new("linear_scan", trains), &app, |b, app| {
+            b.iter(|| linear_scan(&app.ecs))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, signals);
+criterion_main!(benches);
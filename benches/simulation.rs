@@ -0,0 +1,176 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use ratatui::backend::TestBackend;
+use ratatui::style::Color;
+use ratatui::Terminal;
+use specs::{Join, RunNow, WorldExt};
+
+use terminal_transport_game::app::{App, MapOverlay};
+use terminal_transport_game::components::{Point, Traction, TrainColors, TrainParameters, Vehicle};
+use terminal_transport_game::driver_system::DrivingTrain;
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::map_generator::MapGenerator;
+use terminal_transport_game::moving_train_system::MovingTrain;
+use terminal_transport_game::occupancy::Occupancy;
+use terminal_transport_game::replay::WorldSnapshot;
+use terminal_transport_game::track_graph_system::UpdatingTrackGraph;
+use terminal_transport_game::traction_system::ApplyingTraction;
+use terminal_transport_game::traffic_light_system::ActiveTrafficLight;
+use terminal_transport_game::train::{heading, place_train, spawn_train};
+use terminal_transport_game::ui::render_map;
+
+const SEED: u64 = 7;
+/// Ticks run before measuring.
+const WARM_UP_TICKS: usize = 10;
+/// Map sizes along with how many trains run on them.
+const LAYOUTS: [(i32, i32, usize); 3] = [(80, 40, 50), (160, 80, 200), (320, 160, 800)];
+
+/// A generated map with up to `trains` trains running at full throttle.
+fn world(width: i32, height: i32, trains: usize) -> App {
+    let mut app = App::new();
+    let generated = MapGenerator::new(SEED)
+        .size(width, height)
+        .density(1.0)
+        .generate()
+        .expect("The benchmark seed gives a valid map");
+
+    let mut heads: Vec<Point> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Point::new(x, y)))
+        .filter(|&p| {
+            matches!(
+                generated.map.get_tile_at_xy(p).shape(),
+                RailShape::Horizontal | RailShape::Vertical
+            )
+        })
+        .collect();
+    heads.shuffle(&mut ChaCha8Rng::seed_from_u64(SEED));
+    generated.insert_into(&mut app.ecs);
+
+    let vehicles = [
+        Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+        Vehicle::passenger_car(1.5),
+        Vehicle::freight_wagon(3.0),
+    ];
+    let mut spawned = 0;
+    for head in heads {
+        if spawned == trains {
+            break;
+        }
+        let parts = {
+            let map = app.ecs.fetch::<Map>();
+            let occupancy = app.ecs.fetch::<Occupancy>();
+            map.get_tile_at_xy(head)
+                .shape()
+                .connections()
+                .iter()
+                .filter_map(|&d| place_train(&map, head, d, &vehicles))
+                .find(|parts| parts.positions().all(|p| !occupancy.is_occupied(p)))
+        };
+        let Some(parts) = parts else {
+            continue;
+        };
+        let direction = heading(&parts).expect("Trains have three cars");
+        spawn_train(
+            &mut app.ecs,
+            parts,
+            direction,
+            TrainColors {
+                main_color: Color::Blue,
+                head_color: Color::LightBlue,
+            },
+        );
+        spawned += 1;
+    }
+
+    for params in (&mut app.ecs.write_storage::<TrainParameters>()).join() {
+        params.throttle = 1.0;
+    }
+    UpdatingTrackGraph {}.run_now(&app.ecs);
+    // Measured with the trains under way rather than standing at their spawn points
+    for _ in 0..WARM_UP_TICKS {
+        app.tick();
+    }
+    app
+}
+
+/// A world set up like the snapshot, to be run by systems on their own.
+fn restored(snapshot: &WorldSnapshot) -> App {
+    let mut app = App::new();
+    snapshot.restore(&mut app.ecs);
+    UpdatingTrackGraph {}.run_now(&app.ecs);
+    app
+}
+
+fn train_count(app: &App) -> u64 {
+    app.ecs.read_storage::<TrainParameters>().join().count() as u64
+}
+
+/// Drives and moves the trains for a tick, every sample starting from the same world so that
+/// trains piling up at buffers and behind each other don't make later samples cheaper.
+fn moving_train(c: &mut Criterion) {
+    let mut group = c.benchmark_group("moving_train");
+    for (width, height, trains) in LAYOUTS {
+        let app = world(width, height, trains);
+        let snapshot = WorldSnapshot::capture(&app.ecs);
+        group.throughput(Throughput::Elements(train_count(&app)));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}x{}", width, height)),
+            &snapshot,
+            |b, snapshot| {
+                b.iter_batched_ref(
+                    || restored(snapshot),
+                    |app| {
+                        DrivingTrain {}.run_now(&app.ecs);
+                        ApplyingTraction {}.run_now(&app.ecs);
+                        MovingTrain {}.run_now(&app.ecs);
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn traffic_lights(c: &mut Criterion) {
+    let mut group = c.benchmark_group("active_traffic_light");
+    for (width, height, trains) in LAYOUTS {
+        let app = world(width, height, trains);
+        group.throughput(Throughput::Elements(train_count(&app)));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}x{}", width, height)),
+            &app,
+            |b, app| b.iter(|| ActiveTrafficLight {}.run_now(&app.ecs)),
+        );
+    }
+    group.finish();
+}
+
+fn rendering(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_map");
+    for (width, height, trains) in LAYOUTS {
+        let app = world(width, height, trains);
+        let mut terminal = Terminal::new(TestBackend::new(width as u16, height as u16))
+            .expect("The test backend never fails");
+        group.throughput(Throughput::Elements((width * height) as u64));
+        group.bench_function(
+            BenchmarkId::from_parameter(format!("{}x{}", width, height)),
+            |b| {
+                b.iter(|| {
+                    terminal
+                        .draw(|frame| {
+                            frame
+                                .render_widget(render_map(&app.ecs, MapOverlay::None), frame.size())
+                        })
+                        .expect("The test backend never fails");
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, moving_train, traffic_lights, rendering);
+criterion_main!(benches);
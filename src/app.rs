@@ -1,11 +1,34 @@
-use crate::moving_train_system::MovingTrain;
-use crate::traffic_light_system::ActiveTrafficLight;
-use specs::{RunNow, World, WorldExt};
+use crate::components::{
+    Direction, Owner, Point, Route, SignalOverride, Station, Traction, TrafficLight, TrainColors,
+    TrainHead, TrainParameters, TrainParts, Vehicle,
+};
+use crate::glyphs::GlyphSet;
+use crate::keymap::KeyBindings;
+use crate::map::{Map, MapIssue};
+use crate::metrics::Metrics;
+use crate::multiplayer::RemoteGame;
+use crate::occupancy::Occupancy;
+use crate::replay::{Command, Playback, Recording, MAX_REPLAY_SPEED};
+use crate::sim_events::{EventBus, EventFilter, SimClock, SimEventType};
+use crate::systems::{DisabledSystems, SystemsBuilder};
+use crate::theme::Theme;
+use crate::track_graph::TrackGraph;
+use crate::train::place_train;
+use ratatui::layout::Rect;
+use specs::{Dispatcher, Entity, Join, System, SystemData, World, WorldExt};
 use std::error;
 
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
+/// What the map view highlights on top of the track.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MapOverlay {
+    None,
+    /// Colours the track by how steep it is.
+    Gradient,
+}
+
 /// Application.
 // #[derive(Debug)]
 pub struct App {
@@ -14,14 +37,77 @@ pub struct App {
     /// counter
     pub counter: u8,
     pub ecs: World,
+    pub overlay: MapOverlay,
+    /// How many of the newest events the message feed scrolled past.
+    pub feed_scroll: usize,
+    pub feed_filter: EventFilter,
+    /// Tile the player's commands act on.
+    pub cursor: Option<Point>,
+    /// Session being recorded, if any.
+    pub recording: Option<Recording>,
+    /// Recording being played back instead of running the game.
+    pub playback: Option<Playback>,
+    /// Game run by a server instead of this app.
+    pub remote: Option<RemoteGame>,
+    pub key_bindings: KeyBindings,
+    /// Whether the list of key bindings is shown over the map.
+    pub show_help: bool,
+    /// Themes the player can switch between.
+    pub themes: Vec<Theme>,
+    /// Inside of the minimap as last drawn, for clicks to find it.
+    pub minimap_area: Option<Rect>,
+    /// Systems waiting to be built into the dispatcher on the first tick.
+    systems: Option<SystemsBuilder>,
+    dispatcher: Option<Dispatcher<'static, 'static>>,
+    system_names: Vec<&'static str>,
+}
+
+/// An empty world with every component registered and the shared resources in place.
+fn new_world() -> World {
+    let mut ecs = World::new();
+    ecs.register::<Point>();
+    ecs.register::<TrainParts>();
+    ecs.register::<TrainHead>();
+    ecs.register::<TrainColors>();
+    ecs.register::<TrainParameters>();
+    ecs.register::<Direction>();
+    ecs.register::<TrafficLight>();
+    ecs.register::<SignalOverride>();
+    ecs.register::<Station>();
+    ecs.register::<Route>();
+    ecs.register::<Owner>();
+    ecs.insert(TrackGraph::default());
+    ecs.insert(Occupancy::default());
+    ecs.insert(SimClock::default());
+    ecs.insert(EventBus::default());
+    ecs.insert(Metrics::default());
+    ecs.insert(DisabledSystems::default());
+    ecs.insert(Theme::default());
+    ecs.insert(GlyphSet::default());
+    ecs
 }
 
 impl Default for App {
     fn default() -> Self {
+        let systems = SystemsBuilder::standard();
         Self {
             running: true,
             counter: 0,
-            ecs: World::new(),
+            ecs: new_world(),
+            overlay: MapOverlay::None,
+            cursor: None,
+            feed_scroll: 0,
+            feed_filter: EventFilter::default(),
+            system_names: systems.names().to_vec(),
+            systems: Some(systems),
+            dispatcher: None,
+            recording: None,
+            playback: None,
+            remote: None,
+            key_bindings: KeyBindings::default(),
+            show_help: false,
+            themes: Theme::builtin(),
+            minimap_area: None,
         }
     }
 }
@@ -34,7 +120,228 @@ impl App {
 
     /// Handles the tick event of the terminal.
     pub fn tick(&mut self) {
-        self.run_systems()
+        if let Some(remote) = self.remote.as_mut() {
+            if remote.receive() {
+                self.replace_world();
+                if let Some(remote) = &self.remote {
+                    remote.restore(&mut self.ecs);
+                }
+            }
+            return;
+        }
+
+        let Some(mut playback) = self.playback.take() else {
+            self.run_systems();
+            if let Some(recording) = self.recording.as_mut() {
+                recording.ticks += 1;
+            }
+            return;
+        };
+
+        if !playback.paused {
+            for _ in 0..playback.speed {
+                if playback.is_finished(&self.ecs) {
+                    playback.paused = true;
+                    break;
+                }
+                playback.apply_inputs(&mut self.ecs);
+                self.run_systems();
+            }
+        }
+        self.playback = Some(playback);
+    }
+
+    /// Carries out a player command, recording it if the session is recorded.
+    ///
+    /// Commands are ignored while a recording is played back, and sent to the server of a remote game.
+    pub fn execute(&mut self, command: Command) -> bool {
+        if self.playback.is_some() {
+            return false;
+        }
+        if let Some(remote) = self.remote.as_mut() {
+            return remote.send(&command);
+        }
+        let tick = self.ecs.fetch::<SimClock>().tick;
+        let applied = command.apply(&mut self.ecs);
+        if applied {
+            if let Some(recording) = self.recording.as_mut() {
+                recording.record(tick, command);
+            }
+        }
+        applied
+    }
+
+    /// Starts sampling statistics every `interval` ticks.
+    pub fn enable_metrics(&mut self, interval: u64) {
+        self.ecs.insert(Metrics::new(interval));
+    }
+
+    /// Starts recording the session from the current state of the world.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::start(&self.ecs));
+    }
+
+    /// Replaces the world with the start of the recording and plays it back.
+    pub fn start_playback(&mut self, recording: Recording) {
+        let mut playback = Playback::new(recording);
+        self.replace_world();
+        playback.rewind(&mut self.ecs);
+        self.playback = Some(playback);
+    }
+
+    /// Replaces the world with the game on a server, to be kept up to date on every tick.
+    pub fn join(&mut self, remote: RemoteGame) {
+        self.replace_world();
+        remote.restore(&mut self.ecs);
+        self.remote = Some(remote);
+    }
+
+    fn replace_world(&mut self) {
+        let theme = self.ecs.remove::<Theme>().unwrap_or_default();
+        let glyphs = self.ecs.remove::<GlyphSet>().unwrap_or_default();
+        self.ecs = new_world();
+        self.ecs.insert(theme);
+        self.ecs.insert(glyphs);
+        if let Some(dispatcher) = self.dispatcher.as_mut() {
+            dispatcher.setup(&mut self.ecs);
+        }
+    }
+
+    /// Draws the game in the theme with the given name, returning whether there is one.
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        match self.themes.iter().find(|t| t.name == name) {
+            Some(theme) => {
+                self.ecs.insert(theme.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switches to the theme after the current one.
+    pub fn cycle_theme(&mut self) {
+        let current = self.ecs.fetch::<Theme>().name.clone();
+        let next = match self.themes.iter().position(|t| t.name == current) {
+            Some(i) => (i + 1) % self.themes.len(),
+            None => 0,
+        };
+        if let Some(theme) = self.themes.get(next) {
+            self.ecs.insert(theme.clone());
+        }
+    }
+
+    pub fn toggle_replay_pause(&mut self) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.paused = !playback.paused;
+        }
+    }
+
+    /// Doubles or halves the replay speed.
+    pub fn change_replay_speed(&mut self, faster: bool) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.speed = if faster {
+                (playback.speed * 2).min(MAX_REPLAY_SPEED)
+            } else {
+                (playback.speed / 2).max(1)
+            };
+        }
+    }
+
+    /// Jumps `ticks` forward or backward in the replay, simulating again from the start if needed.
+    pub fn seek_replay(&mut self, ticks: i64) {
+        let Some(mut playback) = self.playback.take() else {
+            return;
+        };
+        let current = self.ecs.fetch::<SimClock>().tick;
+        let target = current.saturating_add_signed(ticks).clamp(
+            playback.recording.first_tick(),
+            playback.recording.last_tick(),
+        );
+
+        if target < current {
+            self.replace_world();
+            playback.rewind(&mut self.ecs);
+        }
+        while self.ecs.fetch::<SimClock>().tick < target {
+            playback.apply_inputs(&mut self.ecs);
+            self.run_systems();
+        }
+        self.playback = Some(playback);
+    }
+
+    /// Moves the cursor one tile, putting it in the corner of the map first.
+    pub fn move_cursor(&mut self, direction: Direction) {
+        let map = self.ecs.fetch::<Map>();
+        let next = match self.cursor {
+            None => Point::new(0, 0),
+            Some(p) => p + Point::from(direction),
+        };
+        if map.in_bounds(next) {
+            self.cursor = Some(next);
+        }
+    }
+
+    pub fn toggle_switch_at_cursor(&mut self) {
+        if let Some(p) = self.cursor {
+            self.execute(Command::ToggleSwitch(p));
+        }
+    }
+
+    /// Splits the train under the cursor in front of the car the cursor is on.
+    pub fn uncouple_train_at_cursor(&mut self) {
+        let Some(position) = self.cursor else {
+            return;
+        };
+        let occupant = self.ecs.fetch::<Occupancy>().get(position);
+        if let Some(occupant) = occupant {
+            self.execute(Command::UncoupleTrain {
+                position,
+                at: occupant.car,
+            });
+        }
+    }
+
+    /// Puts a locomotive and a passenger car on the track with the head under the cursor,
+    /// facing whichever way the rest of the train fits behind it.
+    pub fn spawn_train_at_cursor(&mut self) {
+        let Some(head) = self.cursor else {
+            return;
+        };
+        let vehicles = vec![
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ];
+        let direction = {
+            let map = self.ecs.fetch::<Map>();
+            if !map.in_bounds(head) {
+                return;
+            }
+            map.get_tile_at_xy(head)
+                .shape()
+                .connections()
+                .iter()
+                .copied()
+                .find(|&d| place_train(&map, head, d, &vehicles).is_some())
+        };
+        if let Some(direction) = direction {
+            self.execute(Command::SpawnTrain {
+                head,
+                direction,
+                vehicles,
+            });
+        }
+    }
+
+    pub fn reverse_train_at_cursor(&mut self) {
+        if let Some(p) = self.cursor {
+            self.execute(Command::ReverseTrain(p));
+        }
+    }
+
+    pub fn build_track_at_cursor(&mut self) {
+        if let Some(p) = self.cursor {
+            self.execute(Command::BuildTrack(p));
+        }
     }
 
     /// Set running to false to quit the application.
@@ -54,12 +361,115 @@ impl App {
         }
     }
 
-    pub fn run_systems(&mut self) {
-        let mut mts = MovingTrain {};
-        mts.run_now(&self.ecs);
+    /// Checks the map and the signals placed on it for mistakes.
+    pub fn validate_map(&self) -> Vec<MapIssue> {
+        let positions = self.ecs.read_storage::<Point>();
+        let directions = self.ecs.read_storage::<Direction>();
+        let lights = self.ecs.read_storage::<TrafficLight>();
+        let signals: Vec<(Point, Direction)> = (&positions, &directions, &lights)
+            .join()
+            .map(|(&p, &d, _)| (p, d))
+            .collect();
 
-        let mut atl = ActiveTrafficLight {};
-        atl.run_now(&self.ecs);
+        self.ecs.fetch::<Map>().validate(&signals)
+    }
+
+    pub fn toggle_gradient_overlay(&mut self) {
+        self.overlay = match self.overlay {
+            MapOverlay::Gradient => MapOverlay::None,
+            MapOverlay::None => MapOverlay::Gradient,
+        };
+    }
+
+    /// Scrolls the message feed towards older events.
+    pub fn scroll_feed_back(&mut self) {
+        let events = self.ecs.fetch::<EventBus>();
+        let shown = events.latest(&self.feed_filter).count();
+        if self.feed_scroll + 1 < shown {
+            self.feed_scroll += 1;
+        }
+    }
+
+    /// Scrolls the message feed towards the newest events.
+    pub fn scroll_feed_forward(&mut self) {
+        self.feed_scroll = self.feed_scroll.saturating_sub(1);
+    }
+
+    /// Shows only the next type of events in the message feed, then all of them again.
+    pub fn cycle_feed_type_filter(&mut self) {
+        let types = SimEventType::ALL;
+        self.feed_filter.event_type = match self.feed_filter.event_type {
+            None => Some(types[0]),
+            Some(t) => types
+                .iter()
+                .position(|&other| other == t)
+                .and_then(|i| types.get(i + 1))
+                .copied(),
+        };
+        self.feed_scroll = 0;
+    }
+
+    /// Shows only the events of the next train in the message feed, then all of them again.
+    pub fn cycle_feed_train_filter(&mut self) {
+        let entities = self.ecs.entities();
+        let trains = self.ecs.read_storage::<TrainParts>();
+        let mut all: Vec<Entity> = (&entities, &trains).join().map(|(e, _)| e).collect();
+        all.sort_by_key(|e| e.id());
+        self.feed_filter.train = match self.feed_filter.train {
+            None => all.first().copied(),
+            Some(t) => all.iter().find(|e| e.id() > t.id()).copied(),
+        };
+        self.feed_scroll = 0;
+    }
+
+    /// Adds a system to run on every tick after the systems named in `dependencies`.
+    ///
+    /// Panics if the first tick has already happened.
+    pub fn register_system<S>(&mut self, system: S, name: &'static str, dependencies: &[&str])
+    where
+        S: for<'a> System<'a> + Send + 'static,
+        for<'a> <S as System<'a>>::SystemData: SystemData<'a>,
+    {
+        self.systems
+            .as_mut()
+            .expect("Systems must be registered before the first tick")
+            .add(system, name, dependencies);
+        self.system_names.push(name);
+    }
+
+    /// Names of all the registered systems, in the order they were added.
+    pub fn system_names(&self) -> &[&'static str] {
+        &self.system_names
+    }
+
+    pub fn is_system_enabled(&self, name: &str) -> bool {
+        !self.ecs.fetch::<DisabledSystems>().0.contains(name)
+    }
+
+    /// Stops or resumes running a system, for debugging.
+    ///
+    /// Returns `false` if no system of that name is registered.
+    pub fn set_system_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        if !self.system_names.contains(&name) {
+            return false;
+        }
+        let mut disabled = self.ecs.fetch_mut::<DisabledSystems>();
+        if enabled {
+            disabled.0.remove(name);
+        } else {
+            disabled.0.insert(name.to_string());
+        }
+        true
+    }
+
+    pub fn run_systems(&mut self) {
+        if let Some(systems) = self.systems.take() {
+            self.dispatcher = Some(systems.build(&mut self.ecs));
+        }
+        if let Some(dispatcher) = self.dispatcher.as_mut() {
+            dispatcher.dispatch(&self.ecs);
+        }
+        self.ecs.fetch_mut::<SimClock>().tick += 1;
 
         self.ecs.maintain();
     }
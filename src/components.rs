@@ -1,11 +1,13 @@
-use ratatui::layout::Positions;
 use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
 use specs::Component;
 use specs::DenseVecStorage;
 use specs_derive::Component;
+
+use crate::map::Map;
 use std::ops::{Add, AddAssign};
 
-#[derive(Copy, Clone, Component, PartialEq)]
+#[derive(Copy, Clone, Debug, Component, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -22,27 +24,245 @@ pub struct TrainHead {
     pub position: Point,
 }
 
-#[derive(Component)]
+/// Speed up to which a diesel locomotive pulls with its full force.
+const DIESEL_FULL_FORCE_SPEED: f32 = 1.0;
+/// Speed up to which an electric locomotive pulls with its full force.
+const ELECTRIC_FULL_FORCE_SPEED: f32 = 1.5;
+/// Speed at which a steam locomotive can't pull any more.
+const STEAM_TOP_SPEED: f32 = 3.0;
+pub const STEAM_WATER_CAPACITY: f32 = 100.0;
+
+/// How a locomotive produces its tractive force.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Traction {
+    Diesel,
+    /// Needs overhead wires, so it only pulls on electrified track.
+    Electric,
+    /// Uses up water while pulling and has to refill at water supplies.
+    Steam {
+        water: f32,
+    },
+}
+
+impl Traction {
+    pub fn steam() -> Self {
+        Traction::Steam {
+            water: STEAM_WATER_CAPACITY,
+        }
+    }
+
+    /// Force a locomotive capable of `max_force` pulls with at `velocity`.
+    pub fn force(&self, max_force: f32, velocity: f32, electrified: bool) -> f32 {
+        match self {
+            Traction::Diesel => max_force.min(max_force * DIESEL_FULL_FORCE_SPEED / velocity),
+            Traction::Electric if electrified => {
+                max_force.min(max_force * ELECTRIC_FULL_FORCE_SPEED / velocity)
+            }
+            Traction::Electric => 0.0,
+            Traction::Steam { water } if *water > 0.0 => {
+                max_force * (1.0 - velocity / STEAM_TOP_SPEED).max(0.0)
+            }
+            Traction::Steam { .. } => 0.0,
+        }
+    }
+}
+
+/// What kind of rolling stock a single car of a train is.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VehicleKind {
+    Locomotive {
+        traction: Traction,
+        tractive_force: f32,
+    },
+    PassengerCar,
+    FreightWagon,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vehicle {
+    pub kind: VehicleKind,
+    pub mass: f32,
+}
+
+impl Vehicle {
+    pub fn locomotive(traction: Traction, mass: f32, tractive_force: f32) -> Self {
+        Self {
+            kind: VehicleKind::Locomotive {
+                traction,
+                tractive_force,
+            },
+            mass,
+        }
+    }
+
+    pub fn passenger_car(mass: f32) -> Self {
+        Self {
+            kind: VehicleKind::PassengerCar,
+            mass,
+        }
+    }
+
+    pub fn freight_wagon(mass: f32) -> Self {
+        Self {
+            kind: VehicleKind::FreightWagon,
+            mass,
+        }
+    }
+
+    pub fn is_locomotive(&self) -> bool {
+        matches!(self.kind, VehicleKind::Locomotive { .. })
+    }
+
+    pub fn traction(&self) -> Option<Traction> {
+        match self.kind {
+            VehicleKind::Locomotive { traction, .. } => Some(traction),
+            _ => None,
+        }
+    }
+
+    /// Highest force this vehicle can pull with, zero for anything but a locomotive.
+    pub fn tractive_force(&self) -> f32 {
+        match self.kind {
+            VehicleKind::Locomotive { tractive_force, .. } => tractive_force,
+            _ => 0.0,
+        }
+    }
+}
+
+/// A single car of a train standing on a tile.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrainPart {
+    pub position: Point,
+    pub vehicle: Vehicle,
+}
+
+/// The consist of a train, ordered from the head to the tail.
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainParts {
-    pub parts: Vec<Point>,
+    pub parts: Vec<TrainPart>,
 }
 
-#[derive(Component)]
+impl TrainParts {
+    /// Places `vehicles` on `positions`, the first vehicle being the head of the train.
+    pub fn new(positions: &[Point], vehicles: &[Vehicle]) -> Self {
+        assert_eq!(
+            positions.len(),
+            vehicles.len(),
+            "Every vehicle of a train needs exactly one position"
+        );
+        Self {
+            parts: positions
+                .iter()
+                .zip(vehicles)
+                .map(|(&position, &vehicle)| TrainPart { position, vehicle })
+                .collect(),
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Point> + '_ {
+        self.parts.iter().map(|p| p.position)
+    }
+
+    pub fn contains(&self, position: Point) -> bool {
+        self.parts.iter().any(|p| p.position == position)
+    }
+
+    pub fn total_mass(&self) -> f32 {
+        self.parts.iter().map(|p| p.vehicle.mass).sum()
+    }
+
+    pub fn tractive_force(&self) -> f32 {
+        self.parts.iter().map(|p| p.vehicle.tractive_force()).sum()
+    }
+
+    /// Water left in the emptiest steam locomotive of the train, if it has any.
+    pub fn lowest_water(&self) -> Option<f32> {
+        self.parts
+            .iter()
+            .filter_map(|p| match p.vehicle.traction() {
+                Some(Traction::Steam { water }) => Some(water),
+                _ => None,
+            })
+            .reduce(f32::min)
+    }
+
+    /// Whether the train has locomotives, all of which are electric.
+    pub fn needs_electrification(&self) -> bool {
+        let tractions: Vec<Traction> = self
+            .parts
+            .iter()
+            .filter_map(|p| p.vehicle.traction())
+            .collect();
+        !tractions.is_empty() && tractions.iter().all(|&t| t == Traction::Electric)
+    }
+}
+
+#[derive(Component, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainColors {
     pub main_color: Color,
     pub head_color: Color,
 }
 
-#[derive(Component)]
+pub const DEFAULT_ROLLING_RESISTANCE: f32 = 0.05;
+pub const DEFAULT_MAX_SPEED: f32 = 2.0;
+/// Deceleration the brakes of every vehicle are capable of, in tiles per tick squared.
+pub const BRAKE_DECELERATION: f32 = 0.25;
+
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainParameters {
     pub mass: f32,
     pub velocity: f32,
     pub acceleration: f32,
     pub force: f32,
+    pub brake_force: f32,
+    /// Share of the tractive force the driver applies, from 0 to 1.
+    pub throttle: f32,
+    pub braking: bool,
+    pub max_speed: f32,
     pub movement_direction: Direction,
+    /// Rolling resistance per unit of mass.
+    pub rolling_resistance: f32,
+    /// Part of a tile travelled that hasn't been enough to move to the next one yet.
+    pub progress: f32,
+    /// Tiles travelled since the train was created.
+    #[serde(default)]
+    pub odometer: u64,
+}
+
+impl TrainParameters {
+    /// Creates the parameters of a standing train, deriving mass and force from its consist.
+    pub fn new(parts: &TrainParts, movement_direction: Direction) -> Self {
+        Self {
+            mass: parts.total_mass(),
+            velocity: 0.0,
+            acceleration: 0.0,
+            force: parts.tractive_force(),
+            brake_force: parts.total_mass() * BRAKE_DECELERATION,
+            throttle: 1.0,
+            braking: false,
+            max_speed: DEFAULT_MAX_SPEED,
+            movement_direction,
+            rolling_resistance: DEFAULT_ROLLING_RESISTANCE,
+            progress: 0.0,
+            odometer: 0,
+        }
+    }
+
+    /// Recomputes the parameters derived from the consist after it has changed.
+    pub fn update_from_consist(&mut self, parts: &TrainParts) {
+        self.mass = parts.total_mass();
+        self.force = parts.tractive_force();
+        self.brake_force = self.mass * BRAKE_DECELERATION;
+    }
+
+    /// Highest speed the train may run at with its whole length on the given tiles.
+    pub fn speed_limit(&self, map: &Map, parts: &TrainParts) -> f32 {
+        map.lowest_speed_limit(parts.positions())
+            .map_or(self.max_speed, |limit| limit.min(self.max_speed))
+    }
 }
 
-#[derive(Copy, Clone, Debug, Component)]
+#[derive(Copy, Clone, Debug, Component, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Right,
@@ -51,6 +271,13 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
     pub fn opposite(&self) -> Self {
         match self {
             Direction::Up => Direction::Down,
@@ -59,6 +286,17 @@ impl Direction {
             Direction::Left => Direction::Right,
         }
     }
+
+    /// Direction of the step from `from` to the neighbouring tile `to`.
+    pub fn between(from: Point, to: Point) -> Option<Self> {
+        match (to.x - from.x, to.y - from.y) {
+            (0, -1) => Some(Direction::Up),
+            (1, 0) => Some(Direction::Right),
+            (0, 1) => Some(Direction::Down),
+            (-1, 0) => Some(Direction::Left),
+            _ => None,
+        }
+    }
 }
 
 impl From<Direction> for Point {
@@ -98,7 +336,59 @@ impl AddAssign<Point> for Point {
     }
 }
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrafficLight {
     pub is_green: bool,
 }
+
+/// Holds a signal at the given aspect, whatever the trains in its block.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignalOverride {
+    pub is_green: bool,
+}
+
+/// Player a train belongs to in a multiplayer game.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Owner {
+    pub player: u8,
+}
+
+/// A named place trains can be sent to, placed on a track tile with a [`Point`].
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Station {
+    pub name: String,
+}
+
+/// What a route is optimised for.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RouteCost {
+    /// Fewest tiles travelled.
+    Shortest,
+    /// Least time taken, given the speed limits on the way.
+    Fastest,
+}
+
+/// Where a train is heading and the tiles it is going to pass on the way.
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub destination: Point,
+    pub cost: RouteCost,
+    /// Tiles from the head of the train to the destination, empty until the route is planned.
+    pub path: Vec<Point>,
+    pub arrived: bool,
+    /// Tick before which the route is not planned again after the destination couldn't be reached.
+    #[serde(default)]
+    pub retry_at: u64,
+}
+
+impl Route {
+    pub fn new(destination: Point, cost: RouteCost) -> Self {
+        Self {
+            destination,
+            cost,
+            path: Vec::new(),
+            arrived: false,
+            retry_at: 0,
+        }
+    }
+}
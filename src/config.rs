@@ -0,0 +1,18 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Directory the game's settings are kept in, `terminal-transport-game` inside the
+/// user's config directory.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("terminal-transport-game"))
+}
+
+/// Path of a settings file, if it exists.
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|d| d.join(name)).filter(|p| p.is_file())
+}
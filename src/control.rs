@@ -0,0 +1,208 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::app::{App, AppResult};
+use crate::components::{Direction, Point, Traction, Vehicle};
+use crate::env::Observation;
+use crate::occupancy::Occupancy;
+use crate::replay::Command;
+use crate::sim_events::SimClock;
+
+/// Rolling stock a train can be spawned with.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Car {
+    Diesel,
+    Electric,
+    Steam,
+    Passenger,
+    Freight,
+}
+
+impl From<Car> for Vehicle {
+    fn from(car: Car) -> Self {
+        match car {
+            Car::Diesel => Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Car::Electric => Vehicle::locomotive(Traction::Electric, 2.0, 3.0),
+            Car::Steam => Vehicle::locomotive(Traction::steam(), 2.5, 4.0),
+            Car::Passenger => Vehicle::passenger_car(1.5),
+            Car::Freight => Vehicle::freight_wagon(3.0),
+        }
+    }
+}
+
+fn one_tick() -> u64 {
+    1
+}
+
+/// A line sent by a controlling process, e.g. `{"command": "step", "ticks": 10}`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    /// Puts a standing train on the track, the first car being the head.
+    SpawnTrain {
+        head: Point,
+        direction: Direction,
+        cars: Vec<Car>,
+    },
+    /// Holds a signal red or green, or lets the trains set it again if `green` is missing.
+    SetSignal {
+        position: Point,
+        direction: Direction,
+        #[serde(default)]
+        green: Option<bool>,
+    },
+    SetSwitch {
+        position: Point,
+        switched: bool,
+    },
+    Step {
+        #[serde(default = "one_tick")]
+        ticks: u64,
+    },
+    Query,
+    /// Ends the session.
+    Quit,
+}
+
+/// The answer to a request, written back as a single line.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn ok(result: Option<Value>) -> Self {
+        Self {
+            ok: true,
+            result,
+            error: None,
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            result: None,
+            error: Some(message.into()),
+        }
+    }
+}
+
+/// Carries out a request on the app.
+pub fn handle(app: &mut App, request: Request) -> Response {
+    let command = match request {
+        Request::SpawnTrain {
+            head,
+            direction,
+            cars,
+        } => {
+            let command = Command::SpawnTrain {
+                head,
+                direction,
+                vehicles: cars.into_iter().map(Vehicle::from).collect(),
+            };
+            if !app.execute(command) {
+                return Response::error("The train doesn't fit on the track there");
+            }
+            let train = app.ecs.fetch::<Occupancy>().train_at(head);
+            let id = train.map(|t| t.id());
+            return Response::ok(Some(serde_json::json!({ "train": id })));
+        }
+        Request::SetSignal {
+            position,
+            direction,
+            green,
+        } => Command::SetSignal {
+            position,
+            direction,
+            is_green: green,
+        },
+        Request::SetSwitch { position, switched } => Command::SetSwitch { position, switched },
+        Request::Step { ticks } => {
+            for _ in 0..ticks {
+                app.tick();
+            }
+            let tick = app.ecs.fetch::<SimClock>().tick;
+            return Response::ok(Some(serde_json::json!({ "tick": tick })));
+        }
+        Request::Query => {
+            return match serde_json::to_value(Observation::capture(&app.ecs)) {
+                Ok(state) => Response::ok(Some(state)),
+                Err(e) => Response::error(e.to_string()),
+            };
+        }
+        Request::Quit => {
+            app.quit();
+            return Response::ok(None);
+        }
+    };
+
+    if app.execute(command) {
+        Response::ok(None)
+    } else {
+        Response::error("Nothing to act on there")
+    }
+}
+
+/// Parses a line of JSON and carries out the request in it.
+pub fn handle_line(app: &mut App, line: &str) -> Response {
+    match serde_json::from_str(line) {
+        Ok(request) => handle(app, request),
+        Err(e) => Response::error(format!("Invalid request: {}", e)),
+    }
+}
+
+/// Answers requests, one per line, until the input ends or the client quits.
+pub fn serve(app: &mut App, input: impl BufRead, mut output: impl Write) -> AppResult<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_line(app, &line);
+        writeln!(output, "{}", serde_json::to_string(&response)?)?;
+        output.flush()?;
+        if !app.running {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Listens on a Unix socket, serving one client after the other until one of them quits.
+///
+/// A client whose connection fails is logged and dropped, the next one is served as usual.
+#[cfg(unix)]
+pub fn serve_socket(app: &mut App, path: &std::path::Path) -> AppResult<()> {
+    use std::io::BufReader;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket left behind by an earlier run would make binding fail
+    if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let serve_client = |app: &mut App, stream: std::io::Result<UnixStream>| -> AppResult<()> {
+        let stream = stream?;
+        serve(app, BufReader::new(stream.try_clone()?), stream)
+    };
+    for stream in listener.incoming() {
+        // A client going away mid-request shouldn't take the game down with it
+        if let Err(e) = serve_client(app, stream) {
+            eprintln!("Control client dropped: {}", e);
+        }
+        if !app.running {
+            break;
+        }
+    }
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use specs::{Entities, Entity, Join, ReadExpect, ReadStorage, System, Write, WriteStorage};
+
+use crate::components::{Owner, Point, TrainHead, TrainParameters, TrainParts};
+use crate::map::Map;
+use crate::occupancy::Occupancy;
+use crate::train::heading;
+
+/// Fastest a train may run into another one for them to couple, in tiles per tick.
+///
+/// No more than a tile per tick, so that a coupling train never skips over the tile in front.
+pub const MAX_COUPLING_VELOCITY: f32 = 1.0;
+
+/// Couples trains which gently run into the end of another train on the same track.
+///
+/// Trains of different players never couple, running into one is like running into a buffer stop.
+pub struct CouplingTrains {}
+
+impl<'a> System<'a> for CouplingTrains {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, TrainHead>,
+        WriteStorage<'a, TrainParts>,
+        WriteStorage<'a, TrainParameters>,
+        ReadStorage<'a, Owner>,
+        ReadExpect<'a, Map>,
+        Write<'a, Occupancy>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut heads, mut parts, mut parameters, owners, map, mut occupancy) = data;
+
+        // (pushing train, pushed train, whether the pushed train is met head first)
+        let mut couplings: Vec<(Entity, Entity, bool)> = Vec::new();
+        let mut stopped: Vec<Entity> = Vec::new();
+
+        for (e, head, params) in (&entities, &heads, &parameters).join() {
+            if params.velocity <= 0.0 || params.velocity > MAX_COUPLING_VELOCITY {
+                continue;
+            }
+            let Some(dir) = map.try_next_direction(head.position, params.movement_direction) else {
+                continue;
+            };
+            let ahead = head.position + Point::from(dir);
+
+            let Some(other) = occupancy.get(ahead).filter(|o| o.train != e) else {
+                continue;
+            };
+            let Some(other_parts) = parts.get(other.train) else {
+                continue;
+            };
+            if owners.get(e) != owners.get(other.train) {
+                stopped.push(e);
+                continue;
+            }
+            if other.car + 1 == other_parts.parts.len() {
+                couplings.push((e, other.train, false));
+            } else if other.car == 0 {
+                couplings.push((e, other.train, true));
+            }
+        }
+
+        for e in stopped {
+            if let Some(params) = parameters.get_mut(e) {
+                params.velocity = 0.0;
+                params.acceleration = 0.0;
+            }
+        }
+
+        let mut coupled: Vec<Entity> = Vec::new();
+        for (pushing, pushed, head_first) in couplings {
+            if coupled.contains(&pushing) || coupled.contains(&pushed) {
+                continue;
+            }
+            coupled.push(pushing);
+            coupled.push(pushed);
+
+            let (Some(mut front), Some(pushed_params)) =
+                (parts.remove(pushed), parameters.remove(pushed))
+            else {
+                continue;
+            };
+            // A train met head first keeps moving towards the pushing one
+            let pushed_velocity = if head_first {
+                front.parts.reverse();
+                -pushed_params.velocity
+            } else {
+                pushed_params.velocity
+            };
+
+            let (Some(head), Some(train_parts), Some(params)) = (
+                heads.get_mut(pushing),
+                parts.get_mut(pushing),
+                parameters.get_mut(pushing),
+            ) else {
+                continue;
+            };
+
+            let momentum = params.mass * params.velocity + pushed_params.mass * pushed_velocity;
+            front.parts.append(&mut train_parts.parts);
+            train_parts.parts = front.parts;
+
+            head.position = train_parts.parts[0].position;
+            if let Some(d) = heading(train_parts) {
+                params.movement_direction = d;
+            }
+            params.update_from_consist(train_parts);
+            params.velocity = (momentum / params.mass).max(0.0);
+            occupancy.place(pushing, train_parts);
+
+            entities
+                .delete(pushed)
+                .expect("Coupled train entity should be alive");
+        }
+    }
+}
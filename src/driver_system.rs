@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use specs::{Join, ReadExpect, ReadStorage, System, WriteStorage};
+
+use crate::components::{
+    Direction, Point, Route, TrafficLight, TrainHead, TrainParameters, TrainParts,
+    STEAM_WATER_CAPACITY,
+};
+use crate::map::Map;
+
+/// Furthest the driver looks ahead, in tiles.
+const MAX_LOOKAHEAD: usize = 64;
+/// How far below the target speed the driver stops accelerating.
+const COASTING_MARGIN: f32 = 0.1;
+/// Water level at which steam trains stop at the next water supply.
+const LOW_WATER: f32 = 0.3 * STEAM_WATER_CAPACITY;
+
+/// Sets throttle and brakes so that trains slow down in time for speed limits,
+/// red lights, water stops, their destination and the end of the track ahead of them.
+pub struct DrivingTrain {}
+
+impl<'a> System<'a> for DrivingTrain {
+    type SystemData = (
+        ReadStorage<'a, TrainHead>,
+        ReadStorage<'a, TrainParts>,
+        WriteStorage<'a, TrainParameters>,
+        ReadStorage<'a, Point>,
+        ReadStorage<'a, Direction>,
+        ReadStorage<'a, TrafficLight>,
+        ReadStorage<'a, Route>,
+        ReadExpect<'a, Map>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (heads, parts, mut parameters, tl_positions, tl_directions, lights, routes, map) = data;
+
+        let signals: HashMap<Point, (Direction, bool)> = (&tl_positions, &tl_directions, &lights)
+            .join()
+            .map(|(&p, &d, l)| (p, (d, l.is_green)))
+            .collect();
+
+        for (head, parts, params, route) in (&heads, &parts, &mut parameters, routes.maybe()).join()
+        {
+            let deceleration = params.brake_force / params.mass;
+            let mut target = params.speed_limit(&map, parts);
+
+            // Steam trains running low on water wait at a water supply until they are full
+            let water = parts.lowest_water().unwrap_or(STEAM_WATER_CAPACITY);
+            let refilling = params.velocity == 0.0
+                && water < STEAM_WATER_CAPACITY
+                && map.reaches_water_supply(parts);
+            let needs_water = water < LOW_WATER || refilling;
+            if refilling {
+                target = 0.0;
+            }
+
+            // Walk the track ahead, every limit restricts the speed by how quickly it can be reached
+            let mut pos = head.position;
+            let mut dir = params.movement_direction;
+            for tiles_ahead in 1..=MAX_LOOKAHEAD {
+                let distance = tiles_ahead as f32 - 1.0 - params.progress;
+                if braking_speed(0.0, distance, deceleration) > params.max_speed {
+                    break;
+                }
+
+                let next_dir = map.try_next_direction(pos, dir);
+                let red_light = matches!(
+                    signals.get(&pos),
+                    Some(&(signal_dir, false)) if Some(signal_dir) == next_dir
+                );
+                let water_stop = needs_water && map.get_tile_at_xy(pos).has_water_supply();
+                let destination = route.is_some_and(|r| r.destination == pos);
+                let Some(d) = next_dir.filter(|&d| {
+                    !red_light
+                        && !water_stop
+                        && !destination
+                        && map.can_train_enter(pos + Point::from(d), d, parts)
+                }) else {
+                    // The train has to stop on this tile
+                    target = target.min(braking_speed(0.0, distance, deceleration));
+                    break;
+                };
+
+                dir = d;
+                pos += Point::from(dir);
+                if let Some(limit) = map.get_tile_at_xy(pos).speed_limit() {
+                    target = target.min(braking_speed(limit, distance, deceleration));
+                }
+            }
+
+            params.braking = params.velocity > target;
+            params.throttle = if params.velocity < target - COASTING_MARGIN {
+                1.0
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+/// Highest speed from which a train can still slow down to `limit` within `distance` tiles.
+fn braking_speed(limit: f32, distance: f32, deceleration: f32) -> f32 {
+    (limit.powi(2) + 2.0 * deceleration * distance.max(0.0)).sqrt()
+}
@@ -0,0 +1,351 @@
+use serde::Serialize;
+use specs::{Join, World, WorldExt};
+
+use crate::app::App;
+use crate::components::{
+    Direction, Point, Route, RouteCost, SignalOverride, Traction, TrafficLight, TrainParameters,
+    TrainParts, Vehicle,
+};
+use crate::map::Map;
+use crate::map_generator::MapGenerator;
+use crate::occupancy::Occupancy;
+use crate::replay::Command;
+use crate::routing::update_arrival;
+use crate::sim_events::{EventBus, SimClock, SimEventKind};
+use crate::systems::ROUTING_TRAIN;
+
+/// Reward for every train reaching its destination.
+pub const ARRIVAL_REWARD: f32 = 1.0;
+/// Penalty for every collision or derailment, which also ends the episode.
+pub const CRASH_PENALTY: f32 = 10.0;
+/// Penalty for every tick a train on its way stands still.
+pub const WAITING_PENALTY: f32 = 0.01;
+
+/// A setting an agent makes before the simulation runs on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Holds a signal red or green, or hands it back to the trains if `is_green` is `None`.
+    SetSignal {
+        position: Point,
+        direction: Direction,
+        is_green: Option<bool>,
+    },
+    SetSwitch {
+        position: Point,
+        switched: bool,
+    },
+}
+
+impl From<Action> for Command {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::SetSignal {
+                position,
+                direction,
+                is_green,
+            } => Command::SetSignal {
+                position,
+                direction,
+                is_green,
+            },
+            Action::SetSwitch { position, switched } => Command::SetSwitch { position, switched },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrainObservation {
+    pub id: u32,
+    /// Positions of the cars, head first.
+    pub cars: Vec<Point>,
+    pub direction: Direction,
+    pub velocity: f32,
+    /// Tiles travelled so far.
+    pub distance: u64,
+    pub destination: Option<Point>,
+    pub arrived: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct SignalObservation {
+    pub position: Point,
+    pub direction: Direction,
+    pub green: bool,
+    /// Whether the signal is held at its aspect by hand.
+    pub manual: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct SwitchObservation {
+    pub position: Point,
+    pub switched: bool,
+}
+
+/// Everything an agent gets to see of the simulation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Observation {
+    pub tick: u64,
+    pub width: i32,
+    pub height: i32,
+    /// Id of the train on every tile, row by row.
+    pub occupancy: Vec<Option<u32>>,
+    pub trains: Vec<TrainObservation>,
+    pub signals: Vec<SignalObservation>,
+    /// Every junction on the map, row by row.
+    pub switches: Vec<SwitchObservation>,
+}
+
+impl Observation {
+    pub fn capture(ecs: &World) -> Self {
+        let entities = ecs.entities();
+        let parts = ecs.read_storage::<TrainParts>();
+        let parameters = ecs.read_storage::<TrainParameters>();
+        let routes = ecs.read_storage::<Route>();
+        let positions = ecs.read_storage::<Point>();
+        let directions = ecs.read_storage::<Direction>();
+        let lights = ecs.read_storage::<TrafficLight>();
+        let overrides = ecs.read_storage::<SignalOverride>();
+        let occupancy = ecs.fetch::<Occupancy>();
+        let map = ecs.fetch::<Map>();
+
+        let trains = (&entities, &parts, &parameters, routes.maybe())
+            .join()
+            .map(|(e, parts, params, route)| TrainObservation {
+                id: e.id(),
+                cars: parts.positions().collect(),
+                direction: params.movement_direction,
+                velocity: params.velocity,
+                distance: params.odometer,
+                destination: route.map(|r| r.destination),
+                arrived: route.is_some_and(|r| r.arrived),
+            })
+            .collect();
+
+        let signals = (&entities, &positions, &directions, &lights)
+            .join()
+            .map(|(e, &position, &direction, light)| SignalObservation {
+                position,
+                direction,
+                green: light.is_green,
+                manual: overrides.contains(e),
+            })
+            .collect();
+
+        let tiles = (0..map.h()).flat_map(|y| (0..map.w()).map(move |x| Point::new(x, y)));
+        let occupancy = tiles
+            .clone()
+            .map(|p| occupancy.train_at(p).map(|t| t.id()))
+            .collect();
+        let switches = tiles
+            .filter_map(|position| {
+                let tile = map.get_tile_at_xy(position);
+                tile.shape().is_junction().then(|| SwitchObservation {
+                    position,
+                    switched: tile.is_switched(),
+                })
+            })
+            .collect();
+
+        Self {
+            tick: ecs.fetch::<SimClock>().tick,
+            width: map.w(),
+            height: map.h(),
+            occupancy,
+            trains,
+            signals,
+            switches,
+        }
+    }
+}
+
+/// Runs episodes on generated maps for agents that dispatch trains without a terminal.
+///
+/// Every train starts at a station and has the next station as its destination. Routing
+/// is switched off, so setting the junctions is up to the agent.
+pub struct Env {
+    width: i32,
+    height: i32,
+    density: f32,
+    trains: usize,
+    ticks_per_step: u64,
+    max_ticks: u64,
+    app: Option<App>,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 20,
+            density: 0.5,
+            trains: 2,
+            ticks_per_step: 1,
+            max_ticks: 2000,
+            app: None,
+        }
+    }
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// How busy the generated layouts get, see [`MapGenerator::density`].
+    pub fn density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// How many trains every episode starts with, fewer if the stations run out of room.
+    pub fn trains(mut self, trains: usize) -> Self {
+        self.trains = trains;
+        self
+    }
+
+    /// Ticks simulated between two actions.
+    pub fn ticks_per_step(mut self, ticks: u64) -> Self {
+        self.ticks_per_step = ticks.max(1);
+        self
+    }
+
+    /// Ticks after which an episode ends.
+    pub fn max_ticks(mut self, ticks: u64) -> Self {
+        self.max_ticks = ticks;
+        self
+    }
+
+    /// The app running the current episode, `None` before the first reset.
+    pub fn app(&self) -> Option<&App> {
+        self.app.as_ref()
+    }
+
+    /// Starts a new episode on the map generated from `seed`, fails if no map could be generated.
+    pub fn reset(&mut self, seed: u64) -> Result<Observation, String> {
+        let mut app = App::new();
+        app.set_system_enabled(ROUTING_TRAIN, false);
+
+        let generated = MapGenerator::new(seed)
+            .size(self.width, self.height)
+            .density(self.density)
+            .generate()?;
+        let stations: Vec<Point> = generated.stations.iter().map(|&(_, p)| p).collect();
+        generated.insert_into(&mut app.ecs);
+
+        let vehicles = vec![
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ];
+        for i in 0..self.trains.min(stations.len()) {
+            let start = stations[i];
+            let destination = stations[(i + 1) % stations.len()];
+            let sides = app
+                .ecs
+                .fetch::<Map>()
+                .get_tile_at_xy(start)
+                .shape()
+                .connections();
+            let spawned = sides.iter().any(|&direction| {
+                Command::SpawnTrain {
+                    head: start,
+                    direction,
+                    vehicles: vehicles.clone(),
+                }
+                .apply(&mut app.ecs)
+            });
+            let train = app.ecs.fetch::<Occupancy>().train_at(start);
+            if let (true, Some(train), false) = (spawned, train, destination == start) {
+                app.ecs
+                    .write_storage::<Route>()
+                    .insert(train, Route::new(destination, RouteCost::Shortest))
+                    .expect("The train was just spawned");
+            }
+        }
+
+        let observation = Observation::capture(&app.ecs);
+        self.app = Some(app);
+        Ok(observation)
+    }
+
+    /// Applies the actions, runs the simulation on and tells how well it went.
+    ///
+    /// The episode is done once every train has arrived, a train crashed or time ran out.
+    ///
+    /// # Panics
+    ///
+    /// If called before [`Env::reset`].
+    pub fn step(&mut self, actions: &[Action]) -> (Observation, f32, bool) {
+        let app = self
+            .app
+            .as_mut()
+            .expect("The environment is reset before the first step");
+        for &action in actions {
+            app.execute(action.into());
+        }
+
+        let mut reward = 0.0;
+        let mut crashed = false;
+        for _ in 0..self.ticks_per_step {
+            let tick = app.ecs.fetch::<SimClock>().tick;
+            app.tick();
+
+            let events = app.ecs.fetch::<EventBus>();
+            let crashes = events
+                .events()
+                .rev()
+                .take_while(|e| e.tick >= tick)
+                .filter(|e| {
+                    matches!(
+                        e.kind,
+                        SimEventKind::Collision { .. } | SimEventKind::Derailment { .. }
+                    )
+                })
+                .count();
+            drop(events);
+            reward -= CRASH_PENALTY * crashes as f32;
+            crashed |= crashes > 0;
+
+            reward += settle_arrivals(&app.ecs, tick);
+            if crashed {
+                break;
+            }
+        }
+
+        let observation = Observation::capture(&app.ecs);
+        let all_arrived = observation
+            .trains
+            .iter()
+            .all(|t| t.destination.is_none() || t.arrived);
+        let done = crashed || all_arrived || observation.tick >= self.max_ticks;
+        (observation, reward, done)
+    }
+}
+
+/// Marks trains standing at their destination as arrived and rewards them,
+/// penalising the trains still on their way for standing.
+fn settle_arrivals(ecs: &World, tick: u64) -> f32 {
+    let entities = ecs.entities();
+    let parts = ecs.read_storage::<TrainParts>();
+    let parameters = ecs.read_storage::<TrainParameters>();
+    let mut routes = ecs.write_storage::<Route>();
+    let mut events = ecs.fetch_mut::<EventBus>();
+
+    let mut reward = 0.0;
+    for (e, parts, params, route) in (&entities, &parts, &parameters, &mut routes).join() {
+        if route.arrived || params.velocity > 0.0 {
+            continue;
+        }
+        if update_arrival(route, parts.parts[0].position, e, tick, &mut events) {
+            reward += ARRIVAL_REWARD;
+        } else {
+            reward -= WAITING_PENALTY;
+        }
+    }
+    reward
+}
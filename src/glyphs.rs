@@ -0,0 +1,138 @@
+use std::env;
+use std::str::FromStr;
+
+use ratatui::symbols::border;
+
+use crate::components::Direction;
+use crate::map_tile::RailShape;
+
+/// Borders drawn with plain ASCII characters.
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Characters the map is drawn with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GlyphSet {
+    /// Box drawing characters and arrows.
+    #[default]
+    Unicode,
+    /// Plain ASCII, for serial consoles and terminals without Unicode fonts or code pages.
+    Ascii,
+}
+
+impl GlyphSet {
+    /// Guesses from the environment whether the terminal can show Unicode.
+    pub fn detect() -> Self {
+        Self::from_env(|name| env::var(name).ok())
+    }
+
+    /// Picks ASCII for a locale without UTF-8, a terminal known not to support it, or the Windows
+    /// console outside of Windows Terminal.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| var(name).filter(|v| !v.is_empty());
+        if let Some(term) = var("TERM") {
+            if matches!(term.as_str(), "dumb" | "vt100" | "vt102" | "vt220") {
+                return GlyphSet::Ascii;
+            }
+        }
+        // The first of these that is set decides the character encoding
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].into_iter().find_map(var);
+        match locale {
+            Some(locale) => {
+                let locale = locale.to_ascii_lowercase();
+                if locale.contains("utf-8") || locale.contains("utf8") {
+                    GlyphSet::Unicode
+                } else {
+                    GlyphSet::Ascii
+                }
+            }
+            None if cfg!(windows) && var("WT_SESSION").is_none() => GlyphSet::Ascii,
+            None => GlyphSet::Unicode,
+        }
+    }
+
+    pub fn track(&self, shape: RailShape) -> char {
+        match self {
+            GlyphSet::Unicode => char::from(shape),
+            GlyphSet::Ascii => match shape {
+                RailShape::Empty => ' ',
+                RailShape::Horizontal | RailShape::EndRight | RailShape::EndLeft => '-',
+                RailShape::Vertical | RailShape::EndTop | RailShape::EndBottom => '|',
+                RailShape::TurnBottomRight | RailShape::TurnTopLeft => '/',
+                RailShape::TurnBottomLeft | RailShape::TurnTopRight => '\\',
+                RailShape::TTop
+                | RailShape::TRight
+                | RailShape::TBottom
+                | RailShape::TLeft
+                | RailShape::Cross => '+',
+            },
+        }
+    }
+
+    /// Locomotive on track of the given shape.
+    pub fn train(&self, shape: RailShape) -> char {
+        match (self, shape) {
+            (_, RailShape::Empty) => ' ',
+            (GlyphSet::Unicode, shape) => shape.get_train_char(),
+            (GlyphSet::Ascii, _) => '#',
+        }
+    }
+
+    /// Car behind the locomotive on track of the given shape.
+    pub fn wagon(&self, shape: RailShape) -> char {
+        match (self, shape) {
+            (_, RailShape::Empty) => ' ',
+            (GlyphSet::Unicode, shape) => shape.get_wagon_char(),
+            (GlyphSet::Ascii, _) => '=',
+        }
+    }
+
+    /// Signal pointing the way it faces, with a different glyph for each aspect.
+    pub fn signal(&self, direction: Direction, is_green: bool) -> char {
+        match (self, is_green, direction) {
+            (GlyphSet::Unicode, true, d) => char::from(d),
+            (GlyphSet::Unicode, false, Direction::Up) => '▲',
+            (GlyphSet::Unicode, false, Direction::Right) => '▶',
+            (GlyphSet::Unicode, false, Direction::Down) => '▼',
+            (GlyphSet::Unicode, false, Direction::Left) => '◀',
+            (GlyphSet::Ascii, true, Direction::Up) => '^',
+            (GlyphSet::Ascii, true, Direction::Right) => '>',
+            (GlyphSet::Ascii, true, Direction::Down) => 'v',
+            (GlyphSet::Ascii, true, Direction::Left) => '<',
+            (GlyphSet::Ascii, false, Direction::Up) => 'A',
+            (GlyphSet::Ascii, false, Direction::Right) => ']',
+            (GlyphSet::Ascii, false, Direction::Down) => 'V',
+            (GlyphSet::Ascii, false, Direction::Left) => '[',
+        }
+    }
+
+    pub fn border(&self) -> border::Set {
+        match self {
+            GlyphSet::Unicode => border::ROUNDED,
+            GlyphSet::Ascii => ASCII_BORDER,
+        }
+    }
+}
+
+impl FromStr for GlyphSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unicode" => Ok(GlyphSet::Unicode),
+            "ascii" => Ok(GlyphSet::Ascii),
+            _ => Err(format!(
+                "Unknown glyph set `{}`, use `unicode` or `ascii`",
+                s
+            )),
+        }
+    }
+}
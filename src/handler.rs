@@ -1,28 +1,76 @@
 use crate::app::{App, AppResult};
-use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::components::Direction;
+use crate::keymap::KeyAction;
+use crate::map::Map;
+use crate::ui::minimap_tile;
+use ratatui::crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+
+/// Ticks skipped by a single seek in a replay.
+const SEEK_STEP: i64 = 50;
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
-    match key_event.code {
-        // Exit application on `ESC` or `q`
-        KeyCode::Esc | KeyCode::Char('q') => {
-            app.quit();
-        }
-        // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
-            }
-        }
+    // Replay controls come first while a replay runs and do nothing otherwise
+    let actions = app.key_bindings.actions_for(&key_event);
+    let action = actions
+        .iter()
+        .find(|a| a.is_replay_control() && app.playback.is_some())
+        .or_else(|| actions.iter().find(|a| !a.is_replay_control()));
+    let Some(&action) = action else {
+        return Ok(());
+    };
+
+    match action {
+        KeyAction::Quit => app.quit(),
+        KeyAction::ToggleHelp => app.show_help = !app.show_help,
+        // Replay handlers
+        KeyAction::ToggleReplayPause => app.toggle_replay_pause(),
+        KeyAction::ReplayFaster => app.change_replay_speed(true),
+        KeyAction::ReplaySlower => app.change_replay_speed(false),
+        KeyAction::SeekBack => app.seek_replay(-SEEK_STEP),
+        KeyAction::SeekForward => app.seek_replay(SEEK_STEP),
         // Counter handlers
-        KeyCode::Right => {
-            app.increment_counter();
-        }
-        KeyCode::Left => {
-            app.decrement_counter();
-        }
-        // Other handlers you could add here.
-        _ => {}
+        KeyAction::IncrementCounter => app.increment_counter(),
+        KeyAction::DecrementCounter => app.decrement_counter(),
+        // Overlay handlers
+        KeyAction::ToggleGradientOverlay => app.toggle_gradient_overlay(),
+        KeyAction::CycleTheme => app.cycle_theme(),
+        // Message feed handlers
+        KeyAction::ScrollFeedBack => app.scroll_feed_back(),
+        KeyAction::ScrollFeedForward => app.scroll_feed_forward(),
+        KeyAction::CycleFeedTypeFilter => app.cycle_feed_type_filter(),
+        KeyAction::CycleFeedTrainFilter => app.cycle_feed_train_filter(),
+        // Cursor handlers
+        KeyAction::CursorLeft => app.move_cursor(Direction::Left),
+        KeyAction::CursorDown => app.move_cursor(Direction::Down),
+        KeyAction::CursorUp => app.move_cursor(Direction::Up),
+        KeyAction::CursorRight => app.move_cursor(Direction::Right),
+        // Command handlers
+        KeyAction::ToggleSwitch => app.toggle_switch_at_cursor(),
+        KeyAction::ReverseTrain => app.reverse_train_at_cursor(),
+        KeyAction::UncoupleTrain => app.uncouple_train_at_cursor(),
+        KeyAction::SpawnTrain => app.spawn_train_at_cursor(),
+        KeyAction::BuildTrack => app.build_track_at_cursor(),
+    }
+    Ok(())
+}
+
+/// Handles the mouse events, a click on the minimap moves the cursor and with it the camera.
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    if mouse_event.kind != MouseEventKind::Down(MouseButton::Left) {
+        return Ok(());
+    }
+    let Some(area) = app.minimap_area else {
+        return Ok(());
+    };
+    let tile = minimap_tile(
+        &app.ecs.fetch::<Map>(),
+        area,
+        mouse_event.column,
+        mouse_event.row,
+    );
+    if tile.is_some() {
+        app.cursor = tile;
     }
     Ok(())
 }
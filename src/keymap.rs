@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::app::AppResult;
+
+/// Name of the key bindings file in the config directory.
+pub const KEYMAP_FILE: &str = "keys.json";
+
+/// Something the player can do with a key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Quit,
+    ToggleHelp,
+    IncrementCounter,
+    DecrementCounter,
+    ToggleGradientOverlay,
+    CycleTheme,
+    ScrollFeedBack,
+    ScrollFeedForward,
+    CycleFeedTypeFilter,
+    CycleFeedTrainFilter,
+    CursorLeft,
+    CursorDown,
+    CursorUp,
+    CursorRight,
+    ToggleSwitch,
+    ReverseTrain,
+    UncoupleTrain,
+    SpawnTrain,
+    BuildTrack,
+    ToggleReplayPause,
+    ReplayFaster,
+    ReplaySlower,
+    SeekBack,
+    SeekForward,
+}
+
+impl KeyAction {
+    /// Every action, in the order the help lists them.
+    pub const ALL: [KeyAction; 24] = [
+        KeyAction::Quit,
+        KeyAction::ToggleHelp,
+        KeyAction::CursorLeft,
+        KeyAction::CursorDown,
+        KeyAction::CursorUp,
+        KeyAction::CursorRight,
+        KeyAction::ToggleSwitch,
+        KeyAction::ReverseTrain,
+        KeyAction::UncoupleTrain,
+        KeyAction::SpawnTrain,
+        KeyAction::BuildTrack,
+        KeyAction::ToggleGradientOverlay,
+        KeyAction::CycleTheme,
+        KeyAction::ScrollFeedBack,
+        KeyAction::ScrollFeedForward,
+        KeyAction::CycleFeedTypeFilter,
+        KeyAction::CycleFeedTrainFilter,
+        KeyAction::ToggleReplayPause,
+        KeyAction::ReplayFaster,
+        KeyAction::ReplaySlower,
+        KeyAction::SeekBack,
+        KeyAction::SeekForward,
+        KeyAction::IncrementCounter,
+        KeyAction::DecrementCounter,
+    ];
+
+    /// Whether the action only does something while a recording is played back.
+    pub fn is_replay_control(&self) -> bool {
+        matches!(
+            self,
+            KeyAction::ToggleReplayPause
+                | KeyAction::ReplayFaster
+                | KeyAction::ReplaySlower
+                | KeyAction::SeekBack
+                | KeyAction::SeekForward
+        )
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeyAction::Quit => "Quit",
+            KeyAction::ToggleHelp => "Show or hide this help",
+            KeyAction::IncrementCounter => "Increment the counter",
+            KeyAction::DecrementCounter => "Decrement the counter",
+            KeyAction::ToggleGradientOverlay => "Show gradients",
+            KeyAction::CycleTheme => "Switch colour theme",
+            KeyAction::ScrollFeedBack => "Scroll the events back",
+            KeyAction::ScrollFeedForward => "Scroll the events forward",
+            KeyAction::CycleFeedTypeFilter => "Filter events by type",
+            KeyAction::CycleFeedTrainFilter => "Filter events by train",
+            KeyAction::CursorLeft => "Cursor left",
+            KeyAction::CursorDown => "Cursor down",
+            KeyAction::CursorUp => "Cursor up",
+            KeyAction::CursorRight => "Cursor right",
+            KeyAction::ToggleSwitch => "Toggle the switch",
+            KeyAction::ReverseTrain => "Reverse the train",
+            KeyAction::UncoupleTrain => "Uncouple in front of the car",
+            KeyAction::SpawnTrain => "Put a train on the track",
+            KeyAction::BuildTrack => "Build track",
+            KeyAction::ToggleReplayPause => "Pause the replay",
+            KeyAction::ReplayFaster => "Replay faster",
+            KeyAction::ReplaySlower => "Replay slower",
+            KeyAction::SeekBack => "Seek back",
+            KeyAction::SeekForward => "Seek forward",
+        }
+    }
+}
+
+/// A key along with the Ctrl and Alt modifiers, written like `q`, `Ctrl-c` or `PageUp`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        // Shift is part of the character already
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        self.code == event.code && self.modifiers & relevant == event.modifiers & relevant
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Self {
+        Key::new(code, KeyModifiers::NONE)
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 13] = [
+    ("Esc", KeyCode::Esc),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Space", KeyCode::Char(' ')),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(r) = rest.strip_prefix("Ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+                .map(|&(_, code)| code)
+                .or_else(|| {
+                    let n = rest.strip_prefix(['F', 'f'])?.parse().ok()?;
+                    Some(KeyCode::F(n))
+                })
+                .ok_or_else(|| format!("Unknown key `{}`", s))?,
+        };
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => match NAMED_KEYS.iter().find(|&&(_, c)| c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// Which keys carry out which actions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    bindings: HashMap<KeyAction, Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let char_keys = |chars: &str| chars.chars().map(|c| Key::from(KeyCode::Char(c))).collect();
+        let bindings = HashMap::from([
+            (
+                KeyAction::Quit,
+                vec![
+                    Key::from(KeyCode::Esc),
+                    Key::from(KeyCode::Char('q')),
+                    Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+                    Key::new(KeyCode::Char('C'), KeyModifiers::CONTROL),
+                ],
+            ),
+            (KeyAction::ToggleHelp, char_keys("?")),
+            (KeyAction::IncrementCounter, vec![Key::from(KeyCode::Right)]),
+            (KeyAction::DecrementCounter, vec![Key::from(KeyCode::Left)]),
+            (KeyAction::ToggleGradientOverlay, char_keys("g")),
+            (KeyAction::CycleTheme, char_keys("c")),
+            (KeyAction::ScrollFeedBack, vec![Key::from(KeyCode::PageUp)]),
+            (
+                KeyAction::ScrollFeedForward,
+                vec![Key::from(KeyCode::PageDown)],
+            ),
+            (KeyAction::CycleFeedTypeFilter, char_keys("f")),
+            (KeyAction::CycleFeedTrainFilter, char_keys("t")),
+            (KeyAction::CursorLeft, char_keys("h")),
+            (KeyAction::CursorDown, char_keys("j")),
+            (KeyAction::CursorUp, char_keys("k")),
+            (KeyAction::CursorRight, char_keys("l")),
+            (KeyAction::ToggleSwitch, char_keys("x")),
+            (KeyAction::ReverseTrain, char_keys("r")),
+            (KeyAction::UncoupleTrain, char_keys("u")),
+            (KeyAction::SpawnTrain, char_keys("s")),
+            (KeyAction::BuildTrack, char_keys("b")),
+            (KeyAction::ToggleReplayPause, char_keys(" ")),
+            (KeyAction::ReplayFaster, char_keys("+=")),
+            (KeyAction::ReplaySlower, char_keys("-")),
+            (KeyAction::SeekBack, char_keys("<,")),
+            (KeyAction::SeekForward, char_keys(">.")),
+        ]);
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Reads bindings from a JSON file mapping action names to lists of keys, e.g.
+    /// `{"quit": ["q", "Ctrl-c"], "cursor_left": ["Left"]}`.
+    ///
+    /// Actions missing from the file keep their default keys.
+    pub fn load(path: &Path) -> AppResult<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> AppResult<Self> {
+        let overrides: HashMap<KeyAction, Vec<String>> = serde_json::from_str(json)?;
+        let mut bindings = Self::default();
+        for (action, keys) in overrides {
+            let keys = keys
+                .iter()
+                .map(|k| k.parse())
+                .collect::<Result<Vec<Key>, String>>()?;
+            bindings.bindings.insert(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    /// Actions bound to the key that was pressed, in the order the help lists them.
+    pub fn actions_for(&self, event: &KeyEvent) -> Vec<KeyAction> {
+        KeyAction::ALL
+            .into_iter()
+            .filter(|a| self.keys(*a).iter().any(|k| k.matches(event)))
+            .collect()
+    }
+
+    pub fn keys(&self, action: KeyAction) -> &[Key] {
+        self.bindings.get(&action).map_or(&[], |k| k.as_slice())
+    }
+}
@@ -13,15 +13,67 @@ pub mod tui;
 /// Event handler.
 pub mod handler;
 
+/// Keys bound to the player's actions
+pub mod keymap;
+
+/// Where the settings files are kept
+pub mod config;
+
+/// Colours the game is drawn in
+pub mod theme;
+
+/// Characters the game is drawn with
+pub mod glyphs;
+
 /// In-game map
 pub mod map;
 
+/// Procedural map generation
+pub mod map_generator;
+
 ///  Map tiles
 mod map_tile;
 
-/// Train struct
-
 /// Helper components
 pub mod components;
+pub mod coupling_system;
+pub mod driver_system;
+pub mod metrics_system;
 pub mod moving_train_system;
+pub mod routing_system;
+pub mod track_graph_system;
+pub mod traction_system;
 pub mod traffic_light_system;
+
+/// Scheduling of the systems run on every tick
+pub mod systems;
+
+/// Events published by the simulation and the clock they are timed by
+pub mod sim_events;
+
+/// Statistics collected for analysing layouts
+pub mod metrics;
+
+/// Recording sessions and playing them back
+pub mod replay;
+
+/// Driving the simulation from another process with JSON requests
+pub mod control;
+
+/// Episodes for training dispatch agents without a terminal
+pub mod env;
+
+/// Games for two players over TCP
+pub mod multiplayer;
+
+/// Which train is on which tile
+pub mod occupancy;
+
+/// Track network compiled from the map
+pub mod track_graph;
+
+/// Finding routes for trains across the track network
+pub mod routing;
+
+/// Spawning, reversing and splitting trains
+pub mod train;
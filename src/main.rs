@@ -1,4 +1,6 @@
-use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, io, process, thread};
 
 use ratatui::backend::CrosstermBackend;
 use ratatui::style::Color;
@@ -7,46 +9,319 @@ use specs::{Builder, WorldExt};
 
 use terminal_transport_game::app::{App, AppResult};
 use terminal_transport_game::components::{
-    Direction, Point, TrafficLight, TrainColors, TrainHead, TrainParameters, TrainParts,
+    Direction, Point, Station, Traction, TrafficLight, TrainColors, TrainParts, Vehicle,
 };
+use terminal_transport_game::config::{config_dir, config_file};
+use terminal_transport_game::control;
 use terminal_transport_game::event::{Event, EventHandler};
-use terminal_transport_game::handler::handle_key_events;
+use terminal_transport_game::glyphs::GlyphSet;
+use terminal_transport_game::handler::{handle_key_events, handle_mouse_events};
+use terminal_transport_game::keymap::{KeyBindings, KEYMAP_FILE};
 use terminal_transport_game::map::Map;
+use terminal_transport_game::map_generator::MapGenerator;
+use terminal_transport_game::metrics::Metrics;
+use terminal_transport_game::multiplayer::{RemoteGame, Server, DEFAULT_PORT};
+use terminal_transport_game::replay::Recording;
+use terminal_transport_game::theme::{Theme, THEMES_DIR};
+use terminal_transport_game::train::{heading, place_train, spawn_train};
 use terminal_transport_game::tui::Tui;
 
+const USAGE: &str = "Usage: terminal-transport-game [validate] [--seed <number>] \
+[--record <file>] [--replay <file>] [--metrics <file.csv|file.json>] [--metrics-interval <ticks>] \
+[--headless] [--ticks <number>] [--control <stdio|socket path>] [--serve <port>] \
+[--connect <address>] [--theme <name|file>] [--glyphs <unicode|ascii>]";
+const DEFAULT_METRICS_INTERVAL: u64 = 10;
+/// How long a headless run lasts unless told otherwise.
+const DEFAULT_HEADLESS_TICKS: u64 = 1000;
+/// Time between two ticks of the game, in milliseconds.
+const TICK_RATE: u64 = 250;
+
 fn main() -> AppResult<()> {
+    let mut validate_only = false;
+    let mut seed: Option<u64> = None;
+    let mut record: Option<PathBuf> = None;
+    let mut replay: Option<PathBuf> = None;
+    let mut metrics: Option<PathBuf> = None;
+    let mut metrics_interval = DEFAULT_METRICS_INTERVAL;
+    let mut headless = false;
+    let mut ticks = DEFAULT_HEADLESS_TICKS;
+    let mut control: Option<String> = None;
+    let mut serve: Option<u16> = None;
+    let mut connect: Option<String> = None;
+    let mut theme: Option<String> = None;
+    let mut glyphs: Option<GlyphSet> = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "validate" => validate_only = true,
+            "--seed" => seed = Some(number_after(&arg, args.next())),
+            "--metrics-interval" => metrics_interval = number_after(&arg, args.next()).max(1),
+            "--ticks" => ticks = number_after(&arg, args.next()),
+            "--headless" => headless = true,
+            "--control" => {
+                let Some(target) = args.next() else {
+                    eprintln!("--control needs `stdio` or the path of a socket");
+                    process::exit(2);
+                };
+                control = Some(target);
+            }
+            "--serve" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(port) => serve = Some(port),
+                None => {
+                    eprintln!("--serve needs a port, e.g. {}", DEFAULT_PORT);
+                    process::exit(2);
+                }
+            },
+            "--connect" => {
+                let Some(address) = args.next() else {
+                    eprintln!(
+                        "--connect needs an address, e.g. localhost:{}",
+                        DEFAULT_PORT
+                    );
+                    process::exit(2);
+                };
+                connect = Some(address);
+            }
+            "--theme" => {
+                let Some(name) = args.next() else {
+                    eprintln!("--theme needs the name of a theme or a theme file");
+                    process::exit(2);
+                };
+                theme = Some(name);
+            }
+            "--glyphs" => match args.next().map(|s| s.parse()) {
+                Some(Ok(set)) => glyphs = Some(set),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+                None => {
+                    eprintln!("--glyphs needs `unicode` or `ascii`");
+                    process::exit(2);
+                }
+            },
+            "--record" | "--replay" | "--metrics" => {
+                let Some(path) = args.next() else {
+                    eprintln!("{} needs a file", arg);
+                    process::exit(2);
+                };
+                let path = Some(PathBuf::from(path));
+                match arg.as_str() {
+                    "--record" => record = path,
+                    "--replay" => replay = path,
+                    _ => metrics = path,
+                }
+            }
+            other => {
+                eprintln!("Unknown argument: {}", other);
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
     // Create an application.
     let mut app = App::new();
-    app.ecs.insert(Map::simple_ring(20, 10));
+    app.ecs.insert(glyphs.unwrap_or_else(GlyphSet::detect));
+    if let Some(path) = config_file(KEYMAP_FILE) {
+        app.key_bindings = KeyBindings::load(&path)
+            .map_err(|e| format!("Invalid key bindings in {}: {}", path.display(), e))?;
+    }
+    if let Some(dir) = config_dir()
+        .map(|d| d.join(THEMES_DIR))
+        .filter(|d| d.is_dir())
+    {
+        let themes = Theme::load_dir(&dir)
+            .map_err(|e| format!("Invalid theme in {}: {}", dir.display(), e))?;
+        // A theme file named like a built-in one replaces it
+        for theme in themes {
+            match app.themes.iter_mut().find(|t| t.name == theme.name) {
+                Some(existing) => *existing = theme,
+                None => app.themes.push(theme),
+            }
+        }
+    }
+    match (&connect, seed) {
+        (Some(address), _) => app.join(RemoteGame::connect(address.as_str())?),
+        (None, Some(seed)) => build_generated_world(&mut app, seed)?,
+        (None, None) => build_world(&mut app),
+    }
+    if let Some(path) = &replay {
+        app.start_playback(Recording::load(path)?);
+    }
+    if let Some(theme) = &theme {
+        if !app.set_theme(theme) {
+            let theme = Theme::load(Path::new(theme))
+                .map_err(|e| format!("No theme called {}: {}", theme, e))?;
+            app.themes.push(theme.clone());
+            app.set_theme(&theme.name);
+        }
+    }
 
-    app.ecs.register::<Point>();
-    app.ecs.register::<TrainParts>();
-    app.ecs.register::<TrainHead>();
-    app.ecs.register::<TrainColors>();
-    app.ecs.register::<TrainParameters>();
-    app.ecs.register::<Direction>();
-    app.ecs.register::<TrafficLight>();
+    // Check the map before any train runs into a broken piece of track
+    let issues = app.validate_map();
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+    if !issues.is_empty() {
+        process::exit(1);
+    }
+    if validate_only {
+        println!("The map is valid");
+        return Ok(());
+    }
 
-    app.ecs
-        .create_entity()
-        .with(TrainHead {
-            position: Point::new(0, 1),
-        })
-        .with(TrainParts {
-            parts: vec![Point::new(0, 1), Point::new(0, 2), Point::new(0, 3)],
-        })
-        .with(TrainColors {
+    if record.is_some() {
+        app.start_recording();
+    }
+    if metrics.is_some() {
+        app.enable_metrics(metrics_interval);
+    }
+
+    // A controlled simulation only moves when it is told to
+    if let Some(target) = &control {
+        serve_control(&mut app, target)?;
+    } else if let Some(port) = serve {
+        run_server(&mut app, port)?;
+    } else if headless {
+        for _ in 0..ticks {
+            app.tick();
+        }
+    } else {
+        run_tui(&mut app)?;
+    }
+
+    if let (Some(path), Some(recording)) = (&record, &app.recording) {
+        recording.save(path)?;
+    }
+    if let Some(path) = &metrics {
+        app.ecs.fetch::<Metrics>().export(path)?;
+    }
+    Ok(())
+}
+
+/// Parses the number following a command line option, exiting if there is none.
+fn number_after(option: &str, value: Option<String>) -> u64 {
+    match value.and_then(|s| s.parse().ok()) {
+        Some(n) => n,
+        None => {
+            eprintln!("{} needs a number", option);
+            process::exit(2);
+        }
+    }
+}
+
+/// Answers control requests on stdin and stdout, or on the Unix socket at `target`.
+fn serve_control(app: &mut App, target: &str) -> AppResult<()> {
+    if target == "stdio" {
+        return control::serve(app, io::stdin().lock(), io::stdout().lock());
+    }
+    #[cfg(unix)]
+    return control::serve_socket(app, target.as_ref());
+    #[cfg(not(unix))]
+    {
+        eprintln!("Control sockets need a Unix system, use --control stdio");
+        process::exit(2);
+    }
+}
+
+/// Runs the game for the players connecting on `port`, until the process is stopped.
+fn run_server(app: &mut App, port: u16) -> AppResult<()> {
+    let mut server = Server::bind(app, ("127.0.0.1", port))?;
+    eprintln!("Waiting for players on {}", server.local_addr()?);
+    loop {
+        server.step(app);
+        thread::sleep(Duration::from_millis(TICK_RATE));
+    }
+}
+
+/// Runs the game in the terminal until the player quits.
+fn run_tui(app: &mut App) -> AppResult<()> {
+    // Initialize the terminal user interface.
+    let backend = CrosstermBackend::new(io::stderr());
+    let terminal = Terminal::new(backend)?;
+    let events = EventHandler::new(TICK_RATE);
+    let mut tui = Tui::new(terminal, events);
+    tui.init()?;
+
+    // Start the main loop.
+    while app.running {
+        // Render the user interface.
+        tui.draw(app)?;
+        // Handle events.
+        match tui.events.next()? {
+            Event::Tick => app.tick(),
+            Event::Key(key_event) => handle_key_events(key_event, app)?,
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, app)?,
+            Event::Resize(width, height) => tui.resize(width, height)?,
+        }
+    }
+
+    // Exit the user interface.
+    tui.exit()?;
+    Ok(())
+}
+
+/// Fills the world with a generated layout and a train waiting at its first station.
+fn build_generated_world(app: &mut App, seed: u64) -> Result<(), String> {
+    let generated = MapGenerator::new(seed).generate()?;
+    let station = generated.stations[0].1;
+    let parts = {
+        let map = &generated.map;
+        let vehicles = [
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ];
+        map.get_tile_at_xy(station)
+            .shape()
+            .connections()
+            .iter()
+            .find_map(|&d| place_train(map, station, d, &vehicles))
+    };
+    generated.insert_into(&mut app.ecs);
+
+    if let Some(parts) = parts {
+        let direction = heading(&parts).expect("The train has two cars");
+        spawn_train(
+            &mut app.ecs,
+            parts,
+            direction,
+            TrainColors {
+                main_color: Color::Blue,
+                head_color: Color::LightBlue,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Fills the world with the demo layout.
+fn build_world(app: &mut App) {
+    let mut map = Map::simple_ring(20, 10);
+    // A hill on the top side of the ring
+    for x in 4..=16 {
+        let height = 6 - (x - 10i32).abs();
+        map.set_elevation(Point::new(x, 0), height as f32 * 0.1);
+    }
+    app.ecs.insert(map);
+
+    let parts = TrainParts::new(
+        &[Point::new(0, 1), Point::new(0, 2), Point::new(0, 3)],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+            Vehicle::passenger_car(1.5),
+        ],
+    );
+    spawn_train(
+        &mut app.ecs,
+        parts,
+        Direction::Up,
+        TrainColors {
             main_color: Color::Blue,
             head_color: Color::LightBlue,
-        })
-        .with(TrainParameters {
-            mass: 5.0,
-            velocity: 0.0,
-            acceleration: 0.0,
-            force: 3.0,
-            movement_direction: Direction::Up,
-        })
-        .build();
+        },
+    );
 
     app.ecs
         .create_entity()
@@ -76,27 +351,11 @@ fn main() -> AppResult<()> {
         .with(Direction::Up)
         .build();
 
-    // Initialize the terminal user interface.
-    let backend = CrosstermBackend::new(io::stderr());
-    let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(250);
-    let mut tui = Tui::new(terminal, events);
-    tui.init()?;
-
-    // Start the main loop.
-    while app.running {
-        // Render the user interface.
-        tui.draw(&mut app)?;
-        // Handle events.
-        match tui.events.next()? {
-            Event::Tick => app.tick(),
-            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
-        }
-    }
-
-    // Exit the user interface.
-    tui.exit()?;
-    Ok(())
+    app.ecs
+        .create_entity()
+        .with(Station {
+            name: String::from("Central"),
+        })
+        .with(Point { x: 10, y: 9 })
+        .build();
 }
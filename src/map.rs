@@ -1,12 +1,97 @@
-use crate::components::{Direction, Point};
-use crate::map_tile::{MapTile, RailShape};
+use crate::components::{Direction, Point, TrainParts};
+pub use crate::map_tile::{MapTile, RailShape};
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+/// Source of map versions, shared by all maps so that no two layouts get the same one.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A problem with the layout of a map that would trip trains up at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapIssue {
+    /// Rails lead off the map or onto an empty tile.
+    DanglingConnection {
+        position: Point,
+        side: Direction,
+    },
+    /// Rails lead onto a tile which doesn't connect back.
+    MismatchedNeighbour {
+        position: Point,
+        side: Direction,
+        neighbour: RailShape,
+    },
+    /// Track not connected to the main network.
+    UnreachableSection {
+        start: Point,
+        size: usize,
+    },
+    SignalOffTrack {
+        position: Point,
+    },
+    /// The signal doesn't face along the rails of its tile.
+    SignalFacingOffTrack {
+        position: Point,
+        direction: Direction,
+    },
+}
+
+impl fmt::Display for MapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapIssue::DanglingConnection { position, side } => write!(
+                f,
+                "({}, {}): rails lead {:?} into nothing",
+                position.x, position.y, side
+            ),
+            MapIssue::MismatchedNeighbour {
+                position,
+                side,
+                neighbour,
+            } => write!(
+                f,
+                "({}, {}): rails lead {:?} onto {:?}, which doesn't connect back",
+                position.x, position.y, side, neighbour
+            ),
+            MapIssue::UnreachableSection { start, size } => write!(
+                f,
+                "({}, {}): section of {} tiles is not connected to the rest of the track",
+                start.x, start.y, size
+            ),
+            MapIssue::SignalOffTrack { position } => {
+                write!(
+                    f,
+                    "({}, {}): signal is not on track",
+                    position.x, position.y
+                )
+            }
+            MapIssue::SignalFacingOffTrack {
+                position,
+                direction,
+            } => write!(
+                f,
+                "({}, {}): signal faces {:?}, away from the rails",
+                position.x, position.y, direction
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     width: usize,
     height: usize,
     tiles: Vec<MapTile>,
     pub occupied_tiles: Vec<bool>,
+    /// Changes whenever the layout of the track does, and differs between maps.
+    #[serde(skip, default = "next_version")]
+    version: u64,
 }
 
 impl Map {
@@ -16,6 +101,7 @@ impl Map {
             height: h as usize,
             tiles: vec![MapTile::from(RailShape::Empty); (w * h) as usize],
             occupied_tiles: vec![false; (w * h) as usize],
+            version: next_version(),
         }
     }
     pub fn simple_ring(w: i32, h: i32) -> Self {
@@ -42,9 +128,21 @@ impl Map {
         self.tiles[self.xy_idx(coords)]
     }
 
-    fn put_tile_at_xy(&mut self, coords: Point, shape: RailShape) {
+    pub fn in_bounds(&self, coords: Point) -> bool {
+        coords.x >= 0 && coords.y >= 0 && coords.x < self.w() && coords.y < self.h()
+    }
+
+    pub fn put_tile_at_xy(&mut self, coords: Point, shape: RailShape) {
         let i = self.xy_idx(coords);
         self.tiles[i] = MapTile::from(shape);
+        self.version = next_version();
+    }
+
+    /// Replaces the tile along with its switch, elevation and the rest.
+    pub fn set_tile_at_xy(&mut self, coords: Point, tile: MapTile) {
+        let i = self.xy_idx(coords);
+        self.tiles[i] = tile;
+        self.version = next_version();
     }
 
     fn put_horizontal_line(&mut self, x1: i32, x2: i32, y: i32) {
@@ -67,31 +165,214 @@ impl Map {
         self.height as i32
     }
 
-    pub fn get_next_direction(&self, pos: Point, direction: Direction) -> Direction {
-        let shape = self.get_tile_at_xy(pos).shape();
-        match (shape, direction) {
-            (RailShape::Horizontal, Direction::Right) => Direction::Right,
-            (RailShape::Horizontal, Direction::Left) => Direction::Left,
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set_elevation(&mut self, coords: Point, elevation: f32) {
+        let i = self.xy_idx(coords);
+        self.tiles[i].set_elevation(elevation);
+    }
 
-            (RailShape::Vertical, Direction::Up) => Direction::Up,
-            (RailShape::Vertical, Direction::Down) => Direction::Down,
+    pub fn set_speed_limit(&mut self, coords: Point, speed_limit: Option<f32>) {
+        let i = self.xy_idx(coords);
+        self.tiles[i].set_speed_limit(speed_limit);
+    }
 
-            (RailShape::TurnTopLeft, Direction::Down) => Direction::Left,
-            (RailShape::TurnTopLeft, Direction::Right) => Direction::Up,
+    pub fn set_electrified(&mut self, coords: Point, electrified: bool) {
+        let i = self.xy_idx(coords);
+        self.tiles[i].set_electrified(electrified);
+    }
 
-            (RailShape::TurnTopRight, Direction::Down) => Direction::Right,
-            (RailShape::TurnTopRight, Direction::Left) => Direction::Up,
+    pub fn set_water_supply(&mut self, coords: Point, water_supply: bool) {
+        let i = self.xy_idx(coords);
+        self.tiles[i].set_water_supply(water_supply);
+    }
 
-            (RailShape::TurnBottomRight, Direction::Up) => Direction::Right,
-            (RailShape::TurnBottomRight, Direction::Left) => Direction::Down,
+    /// Whether any car of the train stands on a tile with a water supply.
+    pub fn reaches_water_supply(&self, parts: &TrainParts) -> bool {
+        parts
+            .positions()
+            .any(|p| self.get_tile_at_xy(p).has_water_supply())
+    }
 
-            (RailShape::TurnBottomLeft, Direction::Up) => Direction::Left,
-            (RailShape::TurnBottomLeft, Direction::Right) => Direction::Down,
+    /// Lowest speed limit on any of the given tiles.
+    pub fn lowest_speed_limit(&self, tiles: impl Iterator<Item = Point>) -> Option<f32> {
+        tiles
+            .filter_map(|p| self.get_tile_at_xy(p).speed_limit())
+            .reduce(f32::min)
+    }
 
-            (_, _) => panic!(
+    /// Rise of the track when moving from `pos` one tile in `direction`.
+    pub fn gradient(&self, pos: Point, direction: Direction) -> f32 {
+        let next = pos + Point::from(direction);
+        if !self.in_bounds(next) {
+            return 0.0;
+        }
+        self.get_tile_at_xy(next).elevation() - self.get_tile_at_xy(pos).elevation()
+    }
+
+    /// Steepest gradient between `pos` and the tiles its rails connect to.
+    pub fn steepness(&self, pos: Point) -> f32 {
+        self.get_tile_at_xy(pos)
+            .shape()
+            .connections()
+            .iter()
+            .map(|&d| self.gradient(pos, d).abs())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn set_switch(&mut self, coords: Point, switched: bool) {
+        let i = self.xy_idx(coords);
+        self.tiles[i].set_switched(switched);
+    }
+
+    pub fn toggle_switch(&mut self, coords: Point) {
+        let switched = self.get_tile_at_xy(coords).is_switched();
+        self.set_switch(coords, !switched);
+    }
+
+    /// Sets the junction at `coords` so a train travelling in `direction` leaves it through `exit`.
+    ///
+    /// Returns `false` if there is no such route across the tile.
+    pub fn set_route(&mut self, coords: Point, direction: Direction, exit: Direction) -> bool {
+        let exits = self.get_tile_at_xy(coords).shape().exits(direction);
+        match exits.iter().position(|&e| e == exit) {
+            Some(i) => {
+                if exits.len() > 1 {
+                    self.set_switch(coords, i == 1);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a train travelling in `direction` can move onto the tile at `coords`.
+    pub fn can_enter(&self, coords: Point, direction: Direction) -> bool {
+        self.in_bounds(coords)
+            && self
+                .get_tile_at_xy(coords)
+                .shape()
+                .connections()
+                .contains(&direction.opposite())
+    }
+
+    /// Like [`Map::can_enter`], also keeping electric trains under the wires.
+    pub fn can_train_enter(&self, coords: Point, direction: Direction, parts: &TrainParts) -> bool {
+        self.can_enter(coords, direction)
+            && (!parts.needs_electrification() || self.get_tile_at_xy(coords).is_electrified())
+    }
+
+    /// Direction a train leaves `pos` in, or `None` if the track ends there.
+    pub fn try_next_direction(&self, pos: Point, direction: Direction) -> Option<Direction> {
+        self.get_tile_at_xy(pos).next_direction(direction)
+    }
+
+    pub fn get_next_direction(&self, pos: Point, direction: Direction) -> Direction {
+        self.try_next_direction(pos, direction).unwrap_or_else(|| {
+            panic!(
                 "Incorrect travel direction and rail shape combination: {:?}, {:?}",
-                shape, direction
-            ),
+                self.get_tile_at_xy(pos).shape(),
+                direction
+            )
+        })
+    }
+
+    /// Checks that all rails connect up and that the given signals sit on the track facing along it.
+    pub fn validate(&self, signals: &[(Point, Direction)]) -> Vec<MapIssue> {
+        let mut issues = Vec::new();
+
+        for y in 0..self.h() {
+            for x in 0..self.w() {
+                let position = Point::new(x, y);
+                for &side in self.get_tile_at_xy(position).shape().connections() {
+                    let next = position + Point::from(side);
+                    if !self.in_bounds(next) {
+                        issues.push(MapIssue::DanglingConnection { position, side });
+                        continue;
+                    }
+                    let neighbour = self.get_tile_at_xy(next).shape();
+                    if neighbour.connections().is_empty() {
+                        issues.push(MapIssue::DanglingConnection { position, side });
+                    } else if !neighbour.connections().contains(&side.opposite()) {
+                        issues.push(MapIssue::MismatchedNeighbour {
+                            position,
+                            side,
+                            neighbour,
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut sections = self.sections();
+        sections.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        issues.extend(
+            sections
+                .iter()
+                .skip(1)
+                .map(|s| MapIssue::UnreachableSection {
+                    start: s[0],
+                    size: s.len(),
+                }),
+        );
+
+        for &(position, direction) in signals {
+            if !self.in_bounds(position)
+                || self
+                    .get_tile_at_xy(position)
+                    .shape()
+                    .connections()
+                    .is_empty()
+            {
+                issues.push(MapIssue::SignalOffTrack { position });
+            } else if !self
+                .get_tile_at_xy(position)
+                .shape()
+                .connections()
+                .contains(&direction)
+            {
+                issues.push(MapIssue::SignalFacingOffTrack {
+                    position,
+                    direction,
+                });
+            }
+        }
+
+        issues
+    }
+
+    /// Groups of track tiles connected to each other, in the order they are found.
+    fn sections(&self) -> Vec<Vec<Point>> {
+        let mut seen: HashSet<Point> = HashSet::new();
+        let mut sections = Vec::new();
+
+        for y in 0..self.h() {
+            for x in 0..self.w() {
+                let start = Point::new(x, y);
+                if seen.contains(&start)
+                    || self.get_tile_at_xy(start).shape().connections().is_empty()
+                {
+                    continue;
+                }
+
+                seen.insert(start);
+                let mut section = Vec::new();
+                let mut stack = vec![start];
+                while let Some(pos) = stack.pop() {
+                    section.push(pos);
+                    for &side in self.get_tile_at_xy(pos).shape().connections() {
+                        let next = pos + Point::from(side);
+                        if self.can_enter(next, side) && seen.insert(next) {
+                            stack.push(next);
+                        }
+                    }
+                }
+                sections.push(section);
+            }
         }
+
+        sections
     }
 }
@@ -0,0 +1,259 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use specs::{Builder, World, WorldExt};
+
+use crate::components::{Direction, Point, Station, TrafficLight};
+use crate::map::{Map, RailShape};
+
+/// Fewest tiles between parallel lines of track.
+const LINE_SPACING: i32 = 3;
+const MIN_SIDING_LENGTH: i32 = 2;
+const MAX_SIDING_LENGTH: i32 = 6;
+/// How many times a layout is generated again if it turns out broken.
+const MAX_ATTEMPTS: u64 = 16;
+
+/// A generated layout: the map along with signals and stations to place on it.
+pub struct GeneratedMap {
+    pub map: Map,
+    pub signals: Vec<(Point, Direction)>,
+    pub stations: Vec<(String, Point)>,
+}
+
+impl GeneratedMap {
+    /// Inserts the map into the world and creates entities for the signals and stations.
+    pub fn insert_into(self, ecs: &mut World) {
+        ecs.insert(self.map);
+
+        for (position, direction) in self.signals {
+            ecs.create_entity()
+                .with(TrafficLight { is_green: true })
+                .with(position)
+                .with(direction)
+                .build();
+        }
+
+        for (name, position) in self.stations {
+            ecs.create_entity()
+                .with(Station { name })
+                .with(position)
+                .build();
+        }
+    }
+}
+
+/// Seeded generator of connected networks with loops, junctions, sidings and stations.
+///
+/// The same seed, size and density always give the same layout.
+pub struct MapGenerator {
+    seed: u64,
+    width: i32,
+    height: i32,
+    density: f32,
+}
+
+impl MapGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            width: 40,
+            height: 20,
+            density: 0.5,
+        }
+    }
+
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.width = width.max(2 * LINE_SPACING);
+        self.height = height.max(2 * LINE_SPACING);
+        self
+    }
+
+    /// How busy the layout gets, from 0 (just a ring) to 1.
+    pub fn density(mut self, density: f32) -> Self {
+        self.density = density.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Generates the layout, fails if none of the attempts came out valid.
+    pub fn generate(&self) -> Result<GeneratedMap, String> {
+        let mut issues = Vec::new();
+        for attempt in 0..MAX_ATTEMPTS {
+            let generated = self.generate_with(self.seed.wrapping_add(attempt));
+            issues = generated.map.validate(&generated.signals);
+            if issues.is_empty() {
+                return Ok(generated);
+            }
+        }
+        Err(format!(
+            "Failed to generate a valid {}x{} map from seed {}: {}",
+            self.width, self.height, self.seed, issues[0]
+        ))
+    }
+
+    fn generate_with(&self, seed: u64) -> GeneratedMap {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let (w, h) = (self.width, self.height);
+
+        // The outer ring keeps everything connected, lines across it make loops
+        let mut map = Map::simple_ring(w, h);
+
+        let rows = self.pick_lines(&mut rng, h);
+        for &y in &rows {
+            for x in 1..w - 1 {
+                map.put_tile_at_xy(Point::new(x, y), RailShape::Horizontal);
+            }
+            map.put_tile_at_xy(Point::new(0, y), RailShape::TRight);
+            map.put_tile_at_xy(Point::new(w - 1, y), RailShape::TLeft);
+        }
+
+        // Columns run between two of the horizontal lines, crossing the ones in between
+        let mut all_rows = vec![0, h - 1];
+        all_rows.extend(&rows);
+        all_rows.sort();
+        for x in self.pick_lines(&mut rng, w) {
+            let first = rng.gen_range(0..all_rows.len() - 1);
+            let last = rng.gen_range(first + 1..all_rows.len());
+            let (top, bottom) = (all_rows[first], all_rows[last]);
+
+            for y in top + 1..bottom {
+                let shape = if all_rows.contains(&y) {
+                    RailShape::Cross
+                } else {
+                    RailShape::Vertical
+                };
+                map.put_tile_at_xy(Point::new(x, y), shape);
+            }
+            map.put_tile_at_xy(Point::new(x, top), RailShape::TBottom);
+            map.put_tile_at_xy(Point::new(x, bottom), RailShape::TTop);
+        }
+
+        let straights = |map: &Map| -> Vec<Point> {
+            (0..h)
+                .flat_map(|y| (0..w).map(move |x| Point::new(x, y)))
+                .filter(|&p| {
+                    matches!(
+                        map.get_tile_at_xy(p).shape(),
+                        RailShape::Horizontal | RailShape::Vertical
+                    )
+                })
+                .collect()
+        };
+
+        let siding_count = (self.density * (w + h) as f32 / 8.0).round() as usize;
+        let mut candidates = straights(&map);
+        candidates.shuffle(&mut rng);
+        let mut sidings = 0;
+        for start in candidates {
+            if sidings >= siding_count {
+                break;
+            }
+            let length = rng.gen_range(MIN_SIDING_LENGTH..=MAX_SIDING_LENGTH);
+            if add_siding(&mut map, &mut rng, start, length) {
+                sidings += 1;
+            }
+        }
+
+        // Stations and signals go on plain track, away from each other
+        let mut free = straights(&map);
+        free.shuffle(&mut rng);
+        let mut taken: Vec<Point> = Vec::new();
+        let mut place = |count: usize, taken: &mut Vec<Point>| -> Vec<Point> {
+            let mut placed = Vec::new();
+            while placed.len() < count {
+                let Some(p) = free.pop() else {
+                    break;
+                };
+                if taken
+                    .iter()
+                    .any(|t| (t.x - p.x).abs() + (t.y - p.y).abs() < 2)
+                {
+                    continue;
+                }
+                taken.push(p);
+                placed.push(p);
+            }
+            placed
+        };
+
+        let station_count = 1 + (self.density * (w + h) as f32 / 12.0).round() as usize;
+        let stations = place(station_count, &mut taken)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (format!("Station {}", i + 1), p))
+            .collect();
+
+        let signal_count = (self.density * (w + h) as f32 / 4.0).round() as usize;
+        let signals = place(signal_count, &mut taken)
+            .into_iter()
+            .map(|p| {
+                let sides = map.get_tile_at_xy(p).shape().connections();
+                (p, *sides.choose(&mut rng).expect("Signals go on track"))
+            })
+            .collect();
+
+        GeneratedMap {
+            map,
+            signals,
+            stations,
+        }
+    }
+
+    /// Picks coordinates for lines across a map `size` tiles wide, keeping them apart.
+    fn pick_lines(&self, rng: &mut ChaCha8Rng, size: i32) -> Vec<i32> {
+        let mut candidates: Vec<i32> = (LINE_SPACING..size - LINE_SPACING).collect();
+        candidates.shuffle(rng);
+        let wanted = (self.density * (size / (2 * LINE_SPACING)) as f32).round() as usize;
+
+        let mut lines: Vec<i32> = Vec::new();
+        for c in candidates {
+            if lines.len() >= wanted {
+                break;
+            }
+            if lines.iter().all(|l| (l - c).abs() >= LINE_SPACING) {
+                lines.push(c);
+            }
+        }
+        lines
+    }
+}
+
+/// Branches a dead-end siding off the straight track at `start` into empty space.
+fn add_siding(map: &mut Map, rng: &mut ChaCha8Rng, start: Point, length: i32) -> bool {
+    let (sides, junctions) = match map.get_tile_at_xy(start).shape() {
+        RailShape::Horizontal => (
+            [Direction::Up, Direction::Down],
+            [RailShape::TTop, RailShape::TBottom],
+        ),
+        RailShape::Vertical => (
+            [Direction::Left, Direction::Right],
+            [RailShape::TLeft, RailShape::TRight],
+        ),
+        _ => return false,
+    };
+    let i = rng.gen_range(0..2);
+    let (side, junction) = (sides[i], junctions[i]);
+
+    // The siding and a tile of space around its end have to be free
+    let tiles: Vec<Point> = (1..=length)
+        .map(|n| start + Point::new(Point::from(side).x * n, Point::from(side).y * n))
+        .collect();
+    let beyond = *tiles.last().expect("Sidings are never empty") + Point::from(side);
+    let free =
+        |p: &Point| map.in_bounds(*p) && map.get_tile_at_xy(*p).shape().connections().is_empty();
+    if !tiles.iter().all(free) || !free(&beyond) {
+        return false;
+    }
+
+    let (straight, end) = match side {
+        Direction::Up => (RailShape::Vertical, RailShape::EndBottom),
+        Direction::Down => (RailShape::Vertical, RailShape::EndTop),
+        Direction::Left => (RailShape::Horizontal, RailShape::EndRight),
+        Direction::Right => (RailShape::Horizontal, RailShape::EndLeft),
+    };
+    map.put_tile_at_xy(start, junction);
+    for &p in &tiles[..tiles.len() - 1] {
+        map.put_tile_at_xy(p, straight);
+    }
+    map.put_tile_at_xy(*tiles.last().expect("Sidings are never empty"), end);
+    true
+}
@@ -1,7 +1,11 @@
-use ratatui::style::Color;
+use crate::components::Direction;
+use serde::{Deserialize, Serialize};
+
+/// Speed limit on corners without one of their own, in tiles per tick.
+pub const TURN_SPEED_LIMIT: f32 = 1.0;
 
 #[repr(usize)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RailShape {
     Empty = 0,
     Horizontal = 1,
@@ -21,14 +25,29 @@ pub enum RailShape {
     EndLeft = 15,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MapTile {
     shape: RailShape,
+    /// Whether a junction on this tile is set to its second route.
+    switched: bool,
+    /// Height of the track, gradients come from the difference between neighbouring tiles.
+    elevation: f32,
+    speed_limit: Option<f32>,
+    electrified: bool,
+    /// Whether steam locomotives can take on water here.
+    water_supply: bool,
 }
 
 impl From<RailShape> for MapTile {
     fn from(value: RailShape) -> Self {
-        Self { shape: value }
+        Self {
+            shape: value,
+            switched: false,
+            elevation: 0.0,
+            speed_limit: None,
+            electrified: false,
+            water_supply: false,
+        }
     }
 }
 
@@ -56,6 +75,113 @@ impl From<RailShape> for char {
 }
 
 impl RailShape {
+    const ALL: [RailShape; 16] = [
+        RailShape::Empty,
+        RailShape::Horizontal,
+        RailShape::Vertical,
+        RailShape::TurnTopRight,
+        RailShape::TurnBottomRight,
+        RailShape::TurnBottomLeft,
+        RailShape::TurnTopLeft,
+        RailShape::TTop,
+        RailShape::TRight,
+        RailShape::TBottom,
+        RailShape::TLeft,
+        RailShape::Cross,
+        RailShape::EndTop,
+        RailShape::EndRight,
+        RailShape::EndBottom,
+        RailShape::EndLeft,
+    ];
+
+    /// The shape whose rails lead out of exactly these sides, in any order.
+    pub fn from_connections(sides: &[Direction]) -> RailShape {
+        *RailShape::ALL
+            .iter()
+            .find(|shape| {
+                let connections = shape.connections();
+                connections.len() == sides.len() && sides.iter().all(|s| connections.contains(s))
+            })
+            .expect("Every set of sides has a shape")
+    }
+
+    /// Sides of the tile the rails lead out of.
+    pub fn connections(&self) -> &'static [Direction] {
+        match self {
+            RailShape::Empty => &[],
+            RailShape::Horizontal => &[Direction::Left, Direction::Right],
+            RailShape::Vertical => &[Direction::Up, Direction::Down],
+            RailShape::TurnTopRight => &[Direction::Up, Direction::Right],
+            RailShape::TurnBottomRight => &[Direction::Down, Direction::Right],
+            RailShape::TurnBottomLeft => &[Direction::Down, Direction::Left],
+            RailShape::TurnTopLeft => &[Direction::Up, Direction::Left],
+            RailShape::TTop => &[Direction::Left, Direction::Up, Direction::Right],
+            RailShape::TRight => &[Direction::Up, Direction::Right, Direction::Down],
+            RailShape::TBottom => &[Direction::Left, Direction::Down, Direction::Right],
+            RailShape::TLeft => &[Direction::Up, Direction::Left, Direction::Down],
+            RailShape::Cross => &[
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ],
+            RailShape::EndTop => &[Direction::Up],
+            RailShape::EndRight => &[Direction::Right],
+            RailShape::EndBottom => &[Direction::Down],
+            RailShape::EndLeft => &[Direction::Left],
+        }
+    }
+
+    pub fn is_turn(&self) -> bool {
+        matches!(
+            self,
+            RailShape::TurnTopRight
+                | RailShape::TurnBottomRight
+                | RailShape::TurnBottomLeft
+                | RailShape::TurnTopLeft
+        )
+    }
+
+    pub fn is_junction(&self) -> bool {
+        matches!(
+            self,
+            RailShape::TTop | RailShape::TRight | RailShape::TBottom | RailShape::TLeft
+        )
+    }
+
+    /// Directions a train travelling in `direction` can leave this tile in.
+    ///
+    /// The straight route comes first, so it is the default route of a junction.
+    /// A buffer stop has no exits, unless the train already faces away from it.
+    pub fn exits(&self, direction: Direction) -> Vec<Direction> {
+        let connections = self.connections();
+        if !connections.contains(&direction.opposite()) {
+            return if connections == [direction] {
+                vec![direction]
+            } else {
+                Vec::new()
+            };
+        }
+        if let RailShape::Cross = self {
+            return vec![direction];
+        }
+
+        let mut exits: Vec<Direction> = connections
+            .iter()
+            .copied()
+            .filter(|&c| c != direction.opposite())
+            .collect();
+        exits.sort_by_key(|&c| c != direction);
+        exits
+    }
+
+    /// Direction a train has to travel in on this tile to leave it through `exit`.
+    pub fn entry_for_exit(&self, exit: Direction) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .find(|&d| self.exits(d).contains(&exit))
+    }
+
     pub fn get_train_char(&self) -> char {
         match self {
             RailShape::Empty => ' ',
@@ -76,6 +202,27 @@ impl RailShape {
             RailShape::EndLeft => '╡',
         }
     }
+
+    pub fn get_wagon_char(&self) -> char {
+        match self {
+            RailShape::Empty => ' ',
+            RailShape::Horizontal => '━',
+            RailShape::Vertical => '┃',
+            RailShape::TurnTopRight => '┗',
+            RailShape::TurnBottomRight => '┏',
+            RailShape::TurnBottomLeft => '┓',
+            RailShape::TurnTopLeft => '┛',
+            RailShape::TTop => '┻',
+            RailShape::TRight => '┣',
+            RailShape::TBottom => '┳',
+            RailShape::TLeft => '┫',
+            RailShape::Cross => '╋',
+            RailShape::EndTop => '╹',
+            RailShape::EndRight => '╺',
+            RailShape::EndBottom => '╻',
+            RailShape::EndLeft => '╸',
+        }
+    }
 }
 
 impl MapTile {
@@ -83,9 +230,79 @@ impl MapTile {
         self.shape.get_train_char()
     }
 
+    pub fn get_wagon_char(&self) -> char {
+        self.shape.get_wagon_char()
+    }
+
     pub fn shape(&self) -> RailShape {
         self.shape
     }
+
+    /// The same tile with its rails relaid as `shape`, keeping its elevation, wires and the rest.
+    pub fn with_shape(self, shape: RailShape) -> Self {
+        Self { shape, ..self }
+    }
+
+    pub fn is_switched(&self) -> bool {
+        self.switched
+    }
+
+    pub fn set_switched(&mut self, switched: bool) {
+        self.switched = switched;
+    }
+
+    pub fn elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    pub fn set_elevation(&mut self, elevation: f32) {
+        self.elevation = elevation;
+    }
+
+    /// Highest speed allowed on this tile, corners are limited unless told otherwise.
+    pub fn speed_limit(&self) -> Option<f32> {
+        self.speed_limit
+            .or(self.shape.is_turn().then_some(TURN_SPEED_LIMIT))
+    }
+
+    pub fn set_speed_limit(&mut self, speed_limit: Option<f32>) {
+        self.speed_limit = speed_limit;
+    }
+
+    pub fn is_electrified(&self) -> bool {
+        self.electrified
+    }
+
+    pub fn set_electrified(&mut self, electrified: bool) {
+        self.electrified = electrified;
+    }
+
+    pub fn has_water_supply(&self) -> bool {
+        self.water_supply
+    }
+
+    pub fn set_water_supply(&mut self, water_supply: bool) {
+        self.water_supply = water_supply;
+    }
+
+    /// How sharply the track bends on this tile, corners add curve resistance.
+    pub fn curvature(&self) -> f32 {
+        if self.shape.is_turn() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Direction a train travelling in `direction` leaves this tile in, following the junction setting.
+    pub fn next_direction(&self, direction: Direction) -> Option<Direction> {
+        let exits = self.shape.exits(direction);
+        match exits.len() {
+            0 => None,
+            1 => Some(exits[0]),
+            _ => Some(exits[self.switched as usize]),
+        }
+    }
 }
 
 impl From<MapTile> for char {
@@ -93,9 +310,3 @@ impl From<MapTile> for char {
         char::from(value.shape)
     }
 }
-
-impl From<MapTile> for Color {
-    fn from(_value: MapTile) -> Self {
-        Color::Rgb(128, 128, 128)
-    }
-}
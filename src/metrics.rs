@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use specs::Entity;
+
+use crate::app::AppResult;
+use crate::components::{Direction, Point};
+
+/// Running totals of a train between samples.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TrainTotals {
+    /// Ticks spent standing still.
+    pub stopped: u64,
+    /// Ticks spent standing at a red signal.
+    pub signal_wait: u64,
+}
+
+/// State of a train at one sampled tick.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrainSample {
+    pub tick: u64,
+    pub train: u32,
+    /// Tells apart trains that were given the same id after another was removed.
+    pub generation: i32,
+    pub speed: f32,
+    /// Tiles travelled so far.
+    pub distance: u64,
+    pub stopped_ticks: u64,
+    pub signal_wait_ticks: u64,
+}
+
+/// State of the block behind a signal at one sampled tick.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BlockSample {
+    pub tick: u64,
+    pub signal_x: i32,
+    pub signal_y: i32,
+    pub direction: Direction,
+    pub occupied: bool,
+    /// Share of the samples so far in which the block was occupied.
+    pub occupancy: f32,
+}
+
+/// Statistics collected while the simulation runs, for plotting layouts against each other.
+#[derive(Default)]
+pub struct Metrics {
+    /// Ticks between two samples, no samples are taken if 0.
+    pub interval: u64,
+    pub train_totals: HashMap<Entity, TrainTotals>,
+    /// How many samples found each block occupied.
+    pub block_counts: HashMap<(Point, Direction), u64>,
+    pub samples_taken: u64,
+    pub trains: Vec<TrainSample>,
+    pub blocks: Vec<BlockSample>,
+}
+
+#[derive(Serialize)]
+struct Export<'a> {
+    trains: &'a [TrainSample],
+    blocks: &'a [BlockSample],
+}
+
+impl Metrics {
+    pub fn new(interval: u64) -> Self {
+        Self {
+            interval,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval > 0
+    }
+
+    /// Writes the samples as JSON if the path ends in `.json`, as CSV otherwise.
+    ///
+    /// CSV goes into two files next to each other, `<name>_trains.csv` and `<name>_blocks.csv`.
+    pub fn export(&self, path: &Path) -> AppResult<()> {
+        if path.extension().is_some_and(|e| e == "json") {
+            let export = Export {
+                trains: &self.trains,
+                blocks: &self.blocks,
+            };
+            serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &export)?;
+            return Ok(());
+        }
+
+        let mut trains = BufWriter::new(File::create(sibling(path, "trains"))?);
+        writeln!(
+            trains,
+            "tick,train,generation,speed,distance,stopped_ticks,signal_wait_ticks"
+        )?;
+        for s in &self.trains {
+            writeln!(
+                trains,
+                "{},{},{},{},{},{},{}",
+                s.tick,
+                s.train,
+                s.generation,
+                s.speed,
+                s.distance,
+                s.stopped_ticks,
+                s.signal_wait_ticks
+            )?;
+        }
+
+        let mut blocks = BufWriter::new(File::create(sibling(path, "blocks"))?);
+        writeln!(
+            blocks,
+            "tick,signal_x,signal_y,direction,occupied,occupancy"
+        )?;
+        for s in &self.blocks {
+            writeln!(
+                blocks,
+                "{},{},{},{:?},{},{}",
+                s.tick, s.signal_x, s.signal_y, s.direction, s.occupied, s.occupancy
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `<dir>/<name>_<table>.csv` for a path `<dir>/<name>.<anything>`.
+fn sibling(path: &Path, table: &str) -> PathBuf {
+    let stem = path.file_stem().map_or_else(
+        || String::from("metrics"),
+        |s| s.to_string_lossy().into_owned(),
+    );
+    path.with_file_name(format!("{}_{}.csv", stem, table))
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::components::{Direction, Point, TrafficLight, TrainHead, TrainParameters};
+use crate::map::Map;
+use crate::metrics::{BlockSample, Metrics, TrainSample};
+use crate::occupancy::Occupancy;
+use crate::sim_events::SimClock;
+use crate::track_graph::TrackGraph;
+
+/// Counts how long trains stand and wait, and samples trains and blocks every few ticks.
+pub struct CollectingMetrics {}
+
+impl<'a> System<'a> for CollectingMetrics {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, TrainHead>,
+        ReadStorage<'a, TrainParameters>,
+        ReadStorage<'a, Point>,
+        ReadStorage<'a, Direction>,
+        ReadStorage<'a, TrafficLight>,
+        ReadExpect<'a, Map>,
+        Read<'a, TrackGraph>,
+        Read<'a, Occupancy>,
+        Read<'a, SimClock>,
+        Write<'a, Metrics>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            heads,
+            parameters,
+            positions,
+            directions,
+            lights,
+            map,
+            graph,
+            occupancy,
+            clock,
+            mut metrics,
+        ) = data;
+        if !metrics.is_enabled() {
+            return;
+        }
+        metrics.train_totals.retain(|&e, _| entities.is_alive(e));
+
+        let red_signals: HashMap<Point, Direction> = (&positions, &directions, &lights)
+            .join()
+            .filter(|(_, _, l)| !l.is_green)
+            .map(|(&p, &d, _)| (p, d))
+            .collect();
+
+        for (e, head, params) in (&entities, &heads, &parameters).join() {
+            let totals = metrics.train_totals.entry(e).or_default();
+            if params.velocity > 0.0 {
+                continue;
+            }
+            totals.stopped += 1;
+            // Trains wait for a red signal on its tile
+            let exit = map.try_next_direction(head.position, params.movement_direction);
+            if exit.is_some() && red_signals.get(&head.position).copied() == exit {
+                totals.signal_wait += 1;
+            }
+        }
+
+        if clock.tick % metrics.interval != 0 {
+            return;
+        }
+        metrics.samples_taken += 1;
+
+        for (e, params) in (&entities, &parameters).join() {
+            let totals = metrics.train_totals.get(&e).copied().unwrap_or_default();
+            metrics.trains.push(TrainSample {
+                tick: clock.tick,
+                train: e.id(),
+                generation: e.gen().id(),
+                speed: params.velocity,
+                distance: params.odometer,
+                stopped_ticks: totals.stopped,
+                signal_wait_ticks: totals.signal_wait,
+            });
+        }
+
+        // Signals held red by hand don't say anything about their block, so look at the block itself
+        for (&p, &d, _) in (&positions, &directions, &lights).join() {
+            let occupied = graph
+                .block_from(p, d)
+                .iter()
+                .any(|&b| occupancy.is_occupied(b));
+            let count = metrics.block_counts.entry((p, d)).or_insert(0);
+            if occupied {
+                *count += 1;
+            }
+            let occupancy = *count as f32 / metrics.samples_taken as f32;
+            metrics.blocks.push(BlockSample {
+                tick: clock.tick,
+                signal_x: p.x,
+                signal_y: p.y,
+                direction: d,
+                occupied,
+                occupancy,
+            });
+        }
+    }
+}
@@ -1,61 +1,170 @@
+use specs::Entities;
 use specs::Join;
+use specs::Read;
 use specs::ReadExpect;
 use specs::System;
+use specs::Write;
 use specs::WriteStorage;
 
 use crate::components::{Direction, Point, TrainHead, TrainParameters, TrainParts};
+use crate::coupling_system::MAX_COUPLING_VELOCITY;
 use crate::map::Map;
+use crate::occupancy::Occupancy;
+use crate::sim_events::{EventBus, SimClock, SimEventKind};
 
 const AIR_RESISTANCE: f32 = 1.0;
+const CURVE_RESISTANCE: f32 = 0.2;
+const GRAVITY: f32 = 1.0;
+/// Fastest a train can run into a buffer stop without derailing.
+const DERAILMENT_VELOCITY: f32 = 1.0;
 
 pub struct MovingTrain {}
 
 impl<'a> System<'a> for MovingTrain {
     type SystemData = (
+        Entities<'a>,
         WriteStorage<'a, TrainHead>,
         WriteStorage<'a, TrainParts>,
         WriteStorage<'a, TrainParameters>,
         ReadExpect<'a, Map>,
+        Write<'a, Occupancy>,
+        Read<'a, SimClock>,
+        Write<'a, EventBus>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut heads, mut parts, mut parameters, map) = data;
+        let (entities, mut heads, mut parts, mut parameters, map, mut occupancy, clock, mut events) =
+            data;
 
-        for (mut head, mut parts, mut params) in (&mut heads, &mut parts, &mut parameters).join() {
-            params.acceleration =
-                (params.force - (AIR_RESISTANCE * params.velocity.powi(2))) / params.mass;
-            params.velocity += params.acceleration;
+        occupancy.fit(&map, (&entities, &parts).join());
+
+        for (e, head, parts, params) in (&entities, &mut heads, &mut parts, &mut parameters).join()
+        {
+            let was_moving = params.velocity > 0.0;
+            let brake = if params.braking {
+                params.brake_force
+            } else {
+                0.0
+            };
+            params.acceleration = (params.force * params.throttle
+                - brake
+                - (AIR_RESISTANCE * params.velocity.powi(2))
+                - track_resistance(parts, params, &map))
+                / params.mass;
+            // Resistance can stop a train, but never pushes it backwards
+            params.velocity =
+                (params.velocity + params.acceleration).clamp(0.0, params.speed_limit(&map, parts));
 
             // Move train according to velocity
-            let cells_travelled = params.velocity as i32;
+            params.progress += params.velocity;
+            let cells_travelled = params.progress as i32;
+            params.progress -= cells_travelled as f32;
 
             for _ in 0..cells_travelled {
-                let d = move_train(
-                    &mut head,
-                    &mut parts,
-                    cells_travelled,
-                    &map,
-                    params.movement_direction,
+                let ahead = map
+                    .try_next_direction(head.position, params.movement_direction)
+                    .map(|d| head.position + Point::from(d));
+                let other = ahead.and_then(|p| occupancy.train_at(p).filter(|&o| o != e));
+                if let Some(other) = other {
+                    // Slow enough to couple with the train ahead, otherwise it is a collision
+                    if params.velocity > MAX_COUPLING_VELOCITY {
+                        params.velocity = 0.0;
+                        params.acceleration = 0.0;
+                        events.publish(
+                            clock.tick,
+                            Some(e),
+                            SimEventKind::Collision {
+                                position: head.position,
+                                other,
+                            },
+                        );
+                    }
+                    params.progress = 0.0;
+                    break;
+                }
+
+                let tail = parts.parts[parts.parts.len() - 1].position;
+                match move_train(head, parts, &map, params.movement_direction) {
+                    Some(d) => {
+                        params.movement_direction = d;
+                        params.odometer += 1;
+                        occupancy.vacate(e, tail);
+                        occupancy.place(e, parts);
+                    }
+                    None => {
+                        // The track ends ahead, the train runs into the buffer stop
+                        if ahead.is_none() && params.velocity > DERAILMENT_VELOCITY {
+                            events.publish(
+                                clock.tick,
+                                Some(e),
+                                SimEventKind::Derailment {
+                                    position: head.position,
+                                },
+                            );
+                        }
+                        params.velocity = 0.0;
+                        params.acceleration = 0.0;
+                        params.progress = 0.0;
+                        break;
+                    }
+                }
+            }
+
+            if was_moving && params.velocity == 0.0 {
+                events.publish(
+                    clock.tick,
+                    Some(e),
+                    SimEventKind::TrainStopped {
+                        position: head.position,
+                    },
                 );
-                params.movement_direction = d;
             }
         }
     }
 }
 
+/// Rolling, curve and grade resistance of the whole train.
+fn track_resistance(parts: &TrainParts, params: &TrainParameters, map: &Map) -> f32 {
+    let mut resistance = params.rolling_resistance * params.mass;
+
+    for (i, part) in parts.parts.iter().enumerate() {
+        let tile = map.get_tile_at_xy(part.position);
+        resistance += CURVE_RESISTANCE * tile.curvature() * part.vehicle.mass;
+
+        // Every car climbs towards the one in front of it, the head towards the next tile
+        let direction = if i == 0 {
+            map.try_next_direction(part.position, params.movement_direction)
+        } else {
+            Direction::between(part.position, parts.parts[i - 1].position)
+        };
+        if let Some(d) = direction {
+            resistance += GRAVITY * map.gradient(part.position, d) * part.vehicle.mass;
+        }
+    }
+
+    resistance
+}
+
+/// Moves the train one tile forward, every car taking the place of the one in front of it.
+///
+/// Returns `None` and leaves the train in place if there is no track to move onto.
 fn move_train(
     head: &mut TrainHead,
     parts: &mut TrainParts,
-    n: i32,
     map: &Map,
     d: Direction,
-) -> Direction {
-    let dir = map.get_next_direction(head.position, d);
+) -> Option<Direction> {
+    let dir = map.try_next_direction(head.position, d)?;
     let delta_pos = Point::from(dir);
+    if !map.can_train_enter(head.position + delta_pos, dir, parts) {
+        return None;
+    }
     head.position += delta_pos;
-    parts.parts.remove(parts.parts.len() - 1);
-    parts.parts.insert(0, parts.parts[0]);
-    parts.parts[0] += delta_pos;
 
-    dir
+    for i in (1..parts.parts.len()).rev() {
+        parts.parts[i].position = parts.parts[i - 1].position;
+    }
+    parts.parts[0].position += delta_pos;
+
+    Some(dir)
 }
@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use specs::{World, WorldExt};
+
+use crate::app::{App, AppResult};
+use crate::components::{Direction, Owner, Point, TrainColors};
+use crate::map::{Map, MapTile};
+use crate::occupancy::Occupancy;
+use crate::replay::{Command, EntitySnapshot, WorldSnapshot};
+use crate::sim_events::SimClock;
+use crate::train::place_train;
+
+pub const DEFAULT_PORT: u16 = 7878;
+pub const MAX_PLAYERS: usize = 2;
+pub const STARTING_MONEY: i64 = 1000;
+pub const TRACK_COST: i64 = 10;
+pub const TRAIN_COST: i64 = 100;
+/// Longest the server waits on a player who doesn't read what it sends before dropping them.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Colours of the trains of every player.
+const PLAYER_COLORS: [(Color, Color); MAX_PLAYERS] = [
+    (Color::Blue, Color::LightBlue),
+    (Color::Red, Color::LightRed),
+];
+
+/// Money and track of the players, kept by the server.
+#[derive(Clone, Debug)]
+pub struct Players {
+    pub balances: Vec<i64>,
+    /// Who built each tile of track, the starting layout belongs to nobody.
+    pub track: HashMap<Point, u8>,
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Self {
+            balances: vec![STARTING_MONEY; MAX_PLAYERS],
+            track: HashMap::new(),
+        }
+    }
+}
+
+/// Carries out a command for `player`, checking it is theirs to give and charging for it.
+pub fn play(app: &mut App, player: u8, command: Command) -> Result<(), String> {
+    let ecs = &app.ecs;
+    let balance = ecs.fetch::<Players>().balances[player as usize];
+    let track_owner = |p: Point| ecs.fetch::<Players>().track.get(&p).copied();
+    let cost = match command {
+        Command::ReverseTrain(p) | Command::UncoupleTrain { position: p, .. } => {
+            let train = ecs.fetch::<Occupancy>().train_at(p);
+            let owner = train.and_then(|t| ecs.read_storage::<Owner>().get(t).copied());
+            if owner.is_some_and(|o| o.player != player) {
+                return Err(String::from("Not your train"));
+            }
+            0
+        }
+        Command::ToggleSwitch(p)
+        | Command::SetSwitch { position: p, .. }
+        | Command::SetSignal { position: p, .. } => {
+            if track_owner(p).is_some_and(|o| o != player) {
+                return Err(String::from("Not your track"));
+            }
+            0
+        }
+        Command::SpawnTrain {
+            head,
+            direction,
+            ref vehicles,
+        } => {
+            let parts = place_train(&ecs.fetch::<Map>(), head, direction, vehicles);
+            if parts.is_some_and(|parts| {
+                parts
+                    .positions()
+                    .any(|p| track_owner(p).is_some_and(|o| o != player))
+            }) {
+                return Err(String::from("Not your track"));
+            }
+            TRAIN_COST
+        }
+        Command::BuildTrack(p) => {
+            // Neighbours that don't lead onto the new tile yet get relaid
+            let relaid: Vec<Point> = {
+                let map = ecs.fetch::<Map>();
+                Direction::ALL
+                    .into_iter()
+                    .map(|d| (p + Point::from(d), d))
+                    .filter(|&(n, d)| {
+                        let connections = if map.in_bounds(n) {
+                            map.get_tile_at_xy(n).shape().connections()
+                        } else {
+                            &[]
+                        };
+                        !connections.is_empty() && !connections.contains(&d.opposite())
+                    })
+                    .map(|(n, _)| n)
+                    .collect()
+            };
+            if relaid
+                .into_iter()
+                .any(|n| track_owner(n).is_some_and(|o| o != player))
+            {
+                return Err(String::from("Not your track"));
+            }
+            TRACK_COST
+        }
+    };
+    if balance < cost {
+        return Err(String::from("Not enough money"));
+    }
+    if !app.execute(command.clone()) {
+        return Err(String::from("Nothing to act on there"));
+    }
+
+    let ecs = &mut app.ecs;
+    ecs.fetch_mut::<Players>().balances[player as usize] -= cost;
+    match command {
+        Command::SpawnTrain { head, .. } => {
+            let train = ecs
+                .fetch::<Occupancy>()
+                .train_at(head)
+                .expect("The train was just spawned");
+            let (main_color, head_color) = PLAYER_COLORS[player as usize];
+            ecs.write_storage::<Owner>()
+                .insert(train, Owner { player })
+                .expect("The train is alive");
+            ecs.write_storage::<TrainColors>()
+                .insert(
+                    train,
+                    TrainColors {
+                        main_color,
+                        head_color,
+                    },
+                )
+                .expect("The train is alive");
+        }
+        Command::BuildTrack(p) => {
+            ecs.fetch_mut::<Players>().track.insert(p, player);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Changes of the world between two ticks.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub tick: u64,
+    /// The whole map, when a player joins or the size of the map changed.
+    map: Option<Map>,
+    /// Index, tile and whether it lies in a block with a red signal, for every changed tile.
+    tiles: Vec<(usize, MapTile, bool)>,
+    entities: Vec<(u32, EntitySnapshot)>,
+    removed: Vec<u32>,
+    pub balances: Vec<i64>,
+}
+
+/// What the server sends, one message per line.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        player: u8,
+    },
+    Update(StateDiff),
+    /// A command of the player was not carried out.
+    Rejected {
+        reason: String,
+    },
+    /// The game is full.
+    Full,
+}
+
+/// The world as last sent by the server, with the entities keyed by id.
+#[derive(Clone, Default)]
+pub struct Mirror {
+    tick: u64,
+    map: Option<Map>,
+    entities: BTreeMap<u32, EntitySnapshot>,
+    balances: Vec<i64>,
+}
+
+impl Mirror {
+    pub fn capture(ecs: &World) -> Self {
+        let balances = ecs
+            .try_fetch::<Players>()
+            .map(|p| p.balances.clone())
+            .unwrap_or_default();
+        Self {
+            tick: ecs.fetch::<SimClock>().tick,
+            map: Some(Map::clone(&ecs.fetch::<Map>())),
+            entities: WorldSnapshot::capture_entities(ecs).into_iter().collect(),
+            balances,
+        }
+    }
+
+    /// What changed from this state to `newer`.
+    pub fn diff(&self, newer: &Mirror) -> StateDiff {
+        let mut diff = StateDiff {
+            tick: newer.tick,
+            balances: newer.balances.clone(),
+            ..StateDiff::default()
+        };
+
+        match (&self.map, &newer.map) {
+            (Some(old), Some(new)) if (old.w(), old.h()) == (new.w(), new.h()) => {
+                for y in 0..new.h() {
+                    for x in 0..new.w() {
+                        let p = Point::new(x, y);
+                        let i = new.xy_idx(p);
+                        let tile = new.get_tile_at_xy(p);
+                        let occupied = new.occupied_tiles[i];
+                        if old.get_tile_at_xy(p) != tile || old.occupied_tiles[i] != occupied {
+                            diff.tiles.push((i, tile, occupied));
+                        }
+                    }
+                }
+            }
+            _ => diff.map = newer.map.clone(),
+        }
+
+        for (&id, entity) in &newer.entities {
+            if self.entities.get(&id) != Some(entity) {
+                diff.entities.push((id, entity.clone()));
+            }
+        }
+        diff.removed = self
+            .entities
+            .keys()
+            .filter(|id| !newer.entities.contains_key(id))
+            .copied()
+            .collect();
+        diff
+    }
+
+    pub fn apply(&mut self, diff: StateDiff) {
+        self.tick = diff.tick;
+        self.balances = diff.balances;
+        if let Some(map) = diff.map {
+            self.map = Some(map);
+        }
+        if let Some(map) = self.map.as_mut() {
+            for (i, tile, occupied) in diff.tiles {
+                let p = Point::new(i as i32 % map.w(), i as i32 / map.w());
+                map.set_tile_at_xy(p, tile);
+                map.occupied_tiles[i] = occupied;
+            }
+        }
+        for id in diff.removed {
+            self.entities.remove(&id);
+        }
+        self.entities.extend(diff.entities);
+    }
+
+    /// Fills an empty world with the state.
+    pub fn restore(&self, ecs: &mut World) {
+        let Some(map) = &self.map else {
+            return;
+        };
+        WorldSnapshot {
+            tick: self.tick,
+            map: map.clone(),
+            entities: self.entities.values().cloned().collect(),
+        }
+        .restore(ecs);
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &impl Serialize) -> io::Result<()> {
+    let line = serde_json::to_string(message)?;
+    writeln!(stream, "{}", line)
+}
+
+/// Reads a message per line on a thread of its own, the receiver disconnects when the stream closes.
+fn read_messages<T>(stream: &TcpStream) -> io::Result<Receiver<T>>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let reader = BufReader::new(stream.try_clone()?);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(message) = line.map(|l| serde_json::from_str(&l)) else {
+                break;
+            };
+            // Lines that don't parse are dropped, a closed receiver ends the thread
+            if let Ok(message) = message {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+struct Connection {
+    player: u8,
+    stream: TcpStream,
+    commands: Receiver<Command>,
+}
+
+/// Runs the authoritative world and keeps the players' copies of it up to date.
+pub struct Server {
+    listener: TcpListener,
+    connections: Vec<Connection>,
+    /// The state the players were last sent.
+    sent: Mirror,
+}
+
+impl Server {
+    pub fn bind(app: &mut App, addr: impl ToSocketAddrs) -> AppResult<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        app.ecs.insert(Players::default());
+        Ok(Self {
+            listener,
+            connections: Vec::new(),
+            sent: Mirror::capture(&app.ecs),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// How many players are connected.
+    pub fn players(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Takes in new players, carries out their commands, runs a tick and sends out the changes.
+    ///
+    /// Players whose connection fails are dropped, the game goes on without them.
+    pub fn step(&mut self, app: &mut App) {
+        self.accept(app);
+        self.run_commands(app);
+        app.tick();
+
+        let current = Mirror::capture(&app.ecs);
+        let update = ServerMessage::Update(self.sent.diff(&current));
+        self.connections
+            .retain_mut(|c| send(&mut c.stream, &update).is_ok());
+        self.sent = current;
+    }
+
+    fn accept(&mut self, app: &App) {
+        // A failed accept is tried again on the next step
+        while let Ok((mut stream, _)) = self.listener.accept() {
+            let configured = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if configured.is_err() {
+                continue;
+            }
+
+            let taken: Vec<u8> = self.connections.iter().map(|c| c.player).collect();
+            let Some(player) = (0..MAX_PLAYERS as u8).find(|p| !taken.contains(p)) else {
+                // Nothing to do if the player left already
+                let _ = send(&mut stream, &ServerMessage::Full);
+                continue;
+            };
+
+            // A new player starts from the whole world
+            let welcome = send(&mut stream, &ServerMessage::Welcome { player }).and_then(|_| {
+                let state = Mirror::default().diff(&Mirror::capture(&app.ecs));
+                send(&mut stream, &ServerMessage::Update(state))
+            });
+            let Ok(commands) = welcome.and_then(|_| read_messages(&stream)) else {
+                continue;
+            };
+            self.connections.push(Connection {
+                player,
+                commands,
+                stream,
+            });
+        }
+    }
+
+    fn run_commands(&mut self, app: &mut App) {
+        self.connections.retain_mut(|c| loop {
+            let command = match c.commands.try_recv() {
+                Ok(command) => command,
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            };
+            if let Err(reason) = play(app, c.player, command) {
+                if send(&mut c.stream, &ServerMessage::Rejected { reason }).is_err() {
+                    return false;
+                }
+            }
+        });
+    }
+}
+
+/// A game running on a server, seen by one of its players.
+pub struct RemoteGame {
+    pub player: u8,
+    pub balances: Vec<i64>,
+    /// Why the last command was turned down, until the next one is sent.
+    pub rejection: Option<String>,
+    pub connected: bool,
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    mirror: Mirror,
+}
+
+impl RemoteGame {
+    /// Joins the game, waiting for the server to send the world.
+    pub fn connect(addr: impl ToSocketAddrs) -> AppResult<Self> {
+        let stream = TcpStream::connect(addr)?;
+        let messages = read_messages(&stream)?;
+        let player = match messages.recv()? {
+            ServerMessage::Welcome { player } => player,
+            ServerMessage::Full => return Err("The game is full".into()),
+            _ => return Err("The server didn't welcome us".into()),
+        };
+        let mut game = Self {
+            player,
+            balances: Vec::new(),
+            rejection: None,
+            connected: true,
+            stream,
+            messages,
+            mirror: Mirror::default(),
+        };
+        match game.messages.recv()? {
+            ServerMessage::Update(state) => game.mirror.apply(state),
+            _ => return Err("The server didn't send the world".into()),
+        }
+        game.balances = game.mirror.balances.clone();
+        Ok(game)
+    }
+
+    /// Sends a command to the server, returns `false` if the connection is lost.
+    pub fn send(&mut self, command: &Command) -> bool {
+        self.rejection = None;
+        self.connected = self.connected && send(&mut self.stream, command).is_ok();
+        self.connected
+    }
+
+    /// Takes in the messages received since the last call, returns whether the world changed.
+    pub fn receive(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.messages.try_recv() {
+                Ok(ServerMessage::Update(diff)) => {
+                    self.mirror.apply(diff);
+                    changed = true;
+                }
+                Ok(ServerMessage::Rejected { reason }) => self.rejection = Some(reason),
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+        self.balances = self.mirror.balances.clone();
+        changed
+    }
+
+    /// Fills an empty world with the game as last received.
+    pub fn restore(&self, ecs: &mut World) {
+        self.mirror.restore(ecs);
+    }
+
+    pub fn balance(&self) -> Option<i64> {
+        self.balances.get(self.player as usize).copied()
+    }
+}
@@ -0,0 +1,100 @@
+use specs::Entity;
+
+use crate::components::{Point, TrainParts};
+use crate::map::Map;
+
+/// A car standing on a tile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Occupant {
+    pub train: Entity,
+    /// Index of the car in the train, the head being 0.
+    pub car: usize,
+}
+
+/// Grid of the map telling which train, if any, is on every tile.
+///
+/// Kept up to date by the movement system and by everything that adds, removes
+/// or rearranges trains, so lookups never have to scan through all the trains.
+#[derive(Default)]
+pub struct Occupancy {
+    width: i32,
+    height: i32,
+    cells: Vec<Option<Occupant>>,
+    /// Version of the map the grid was last filled in for.
+    map_version: Option<u64>,
+}
+
+impl Occupancy {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; (width * height) as usize],
+            map_version: None,
+        }
+    }
+
+    fn idx(&self, position: Point) -> Option<usize> {
+        let inside =
+            (0..self.width).contains(&position.x) && (0..self.height).contains(&position.y);
+        inside.then(|| (position.y * self.width + position.x) as usize)
+    }
+
+    /// Makes the grid match the map, placing all the trains again if the map changed or was replaced.
+    pub fn fit<'a>(&mut self, map: &Map, trains: impl Iterator<Item = (Entity, &'a TrainParts)>) {
+        if self.map_version == Some(map.version()) {
+            return;
+        }
+        *self = Self::new(map.w(), map.h());
+        self.map_version = Some(map.version());
+        for (train, parts) in trains {
+            self.place(train, parts);
+        }
+    }
+
+    pub fn get(&self, position: Point) -> Option<Occupant> {
+        self.idx(position).and_then(|i| self.cells[i])
+    }
+
+    pub fn train_at(&self, position: Point) -> Option<Entity> {
+        self.get(position).map(|o| o.train)
+    }
+
+    pub fn is_occupied(&self, position: Point) -> bool {
+        self.get(position).is_some()
+    }
+
+    /// Tiles under every train but the given one.
+    pub fn occupied_by_others(&self, train: Entity) -> impl Iterator<Item = Point> + '_ {
+        let width = self.width;
+        self.cells.iter().enumerate().filter_map(move |(i, cell)| {
+            cell.filter(|o| o.train != train)
+                .map(|_| Point::new(i as i32 % width, i as i32 / width))
+        })
+    }
+
+    /// Marks the tiles under every car of the train.
+    pub fn place(&mut self, train: Entity, parts: &TrainParts) {
+        for (car, part) in parts.parts.iter().enumerate() {
+            if let Some(i) = self.idx(part.position) {
+                self.cells[i] = Some(Occupant { train, car });
+            }
+        }
+    }
+
+    /// Frees the tile if the train is on it.
+    pub fn vacate(&mut self, train: Entity, position: Point) {
+        if let Some(i) = self.idx(position) {
+            if self.cells[i].is_some_and(|o| o.train == train) {
+                self.cells[i] = None;
+            }
+        }
+    }
+
+    /// Frees the tiles under every car of the train.
+    pub fn remove(&mut self, train: Entity, parts: &TrainParts) {
+        for part in &parts.parts {
+            self.vacate(train, part.position);
+        }
+    }
+}
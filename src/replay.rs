@@ -0,0 +1,442 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use specs::{Builder, Join, World, WorldExt};
+
+use crate::app::AppResult;
+use crate::components::{
+    Direction, Owner, Point, Route, SignalOverride, Station, TrafficLight, TrainColors, TrainHead,
+    TrainParameters, TrainParts, Vehicle,
+};
+use crate::map::{Map, RailShape};
+use crate::occupancy::Occupancy;
+use crate::sim_events::SimClock;
+use crate::train::{place_train, reverse_train, spawn_train, uncouple_train};
+
+/// A player action that changes the simulation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    ToggleSwitch(Point),
+    /// Turns around the train standing on the tile.
+    ReverseTrain(Point),
+    /// Splits the train on the tile in front of its car with index `at`.
+    UncoupleTrain {
+        position: Point,
+        at: usize,
+    },
+    /// Puts a new standing train on the track, its head on `head` travelling in `direction`.
+    SpawnTrain {
+        head: Point,
+        direction: Direction,
+        vehicles: Vec<Vehicle>,
+    },
+    /// Holds a signal red or green, or hands it back to the trains if `is_green` is `None`.
+    SetSignal {
+        position: Point,
+        direction: Direction,
+        is_green: Option<bool>,
+    },
+    SetSwitch {
+        position: Point,
+        switched: bool,
+    },
+    /// Lays track on an empty tile, joining it to the track on every side.
+    BuildTrack(Point),
+}
+
+/// Colours given to trains spawned by commands, taken in turn.
+const SPAWNED_TRAIN_COLORS: [(Color, Color); 3] = [
+    (Color::Magenta, Color::LightMagenta),
+    (Color::Green, Color::LightGreen),
+    (Color::Cyan, Color::LightCyan),
+];
+
+impl Command {
+    /// Carries out the command, returns `false` if there was nothing to act on.
+    pub fn apply(&self, ecs: &mut World) -> bool {
+        match *self {
+            Command::ToggleSwitch(position) => {
+                let mut map = ecs.fetch_mut::<Map>();
+                if !is_junction(&map, position) {
+                    return false;
+                }
+                map.toggle_switch(position);
+                true
+            }
+            Command::ReverseTrain(position) => {
+                let train = ecs.fetch::<Occupancy>().train_at(position);
+                train.is_some_and(|t| reverse_train(ecs, t))
+            }
+            Command::UncoupleTrain { position, at } => {
+                let train = ecs.fetch::<Occupancy>().train_at(position);
+                train.is_some_and(|t| uncouple_train(ecs, t, at).is_some())
+            }
+            Command::SpawnTrain {
+                head,
+                direction,
+                ref vehicles,
+            } => {
+                if vehicles.is_empty() {
+                    return false;
+                }
+                let parts = {
+                    let map = ecs.fetch::<Map>();
+                    let occupancy = ecs.fetch::<Occupancy>();
+                    place_train(&map, head, direction, vehicles)
+                        .filter(|parts| !parts.positions().any(|p| occupancy.is_occupied(p)))
+                };
+                let Some(parts) = parts else {
+                    return false;
+                };
+                let (main_color, head_color) = SPAWNED_TRAIN_COLORS
+                    [ecs.read_storage::<TrainParts>().count() % SPAWNED_TRAIN_COLORS.len()];
+                spawn_train(
+                    ecs,
+                    parts,
+                    direction,
+                    TrainColors {
+                        main_color,
+                        head_color,
+                    },
+                );
+                true
+            }
+            Command::SetSignal {
+                position,
+                direction,
+                is_green,
+            } => {
+                let entities = ecs.entities();
+                let positions = ecs.read_storage::<Point>();
+                let directions = ecs.read_storage::<Direction>();
+                let mut lights = ecs.write_storage::<TrafficLight>();
+                let mut overrides = ecs.write_storage::<SignalOverride>();
+                let Some((signal, _, _, light)) = (&entities, &positions, &directions, &mut lights)
+                    .join()
+                    .find(|(_, &p, &d, _)| p == position && d == direction)
+                else {
+                    return false;
+                };
+                match is_green {
+                    Some(is_green) => {
+                        light.is_green = is_green;
+                        overrides
+                            .insert(signal, SignalOverride { is_green })
+                            .expect("The signal is alive");
+                    }
+                    None => {
+                        overrides.remove(signal);
+                    }
+                }
+                true
+            }
+            Command::SetSwitch { position, switched } => {
+                let mut map = ecs.fetch_mut::<Map>();
+                if !is_junction(&map, position) {
+                    return false;
+                }
+                map.set_switch(position, switched);
+                true
+            }
+            Command::BuildTrack(position) => {
+                let mut map = ecs.fetch_mut::<Map>();
+                if !map.in_bounds(position)
+                    || map.get_tile_at_xy(position).shape() != RailShape::Empty
+                {
+                    return false;
+                }
+                let sides: Vec<Direction> = Direction::ALL
+                    .into_iter()
+                    .filter(|&d| {
+                        let p = position + Point::from(d);
+                        map.in_bounds(p) && map.get_tile_at_xy(p).shape() != RailShape::Empty
+                    })
+                    .collect();
+                if sides.is_empty() {
+                    return false;
+                }
+                let tile = map.get_tile_at_xy(position);
+                map.set_tile_at_xy(
+                    position,
+                    tile.with_shape(RailShape::from_connections(&sides)),
+                );
+                // The neighbours get a way onto the new tile
+                for d in sides {
+                    let p = position + Point::from(d);
+                    let tile = map.get_tile_at_xy(p);
+                    let mut connections = tile.shape().connections().to_vec();
+                    if !connections.contains(&d.opposite()) {
+                        connections.push(d.opposite());
+                        map.set_tile_at_xy(
+                            p,
+                            tile.with_shape(RailShape::from_connections(&connections)),
+                        );
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+fn is_junction(map: &Map, position: Point) -> bool {
+    map.in_bounds(position) && map.get_tile_at_xy(position).shape().is_junction()
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum EntitySnapshot {
+    Train {
+        parts: TrainParts,
+        parameters: TrainParameters,
+        colors: TrainColors,
+        route: Option<Route>,
+        #[serde(default)]
+        owner: Option<Owner>,
+    },
+    Signal {
+        position: Point,
+        direction: Direction,
+        light: TrafficLight,
+        #[serde(default)]
+        manual: Option<SignalOverride>,
+    },
+    Station {
+        position: Point,
+        station: Station,
+    },
+}
+
+/// Everything needed to set the simulation up again exactly as it was.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub(crate) tick: u64,
+    pub(crate) map: Map,
+    /// In the order the entities were created, so that they get the same ids back.
+    pub(crate) entities: Vec<EntitySnapshot>,
+}
+
+impl WorldSnapshot {
+    pub fn capture(ecs: &World) -> Self {
+        Self {
+            tick: ecs.fetch::<SimClock>().tick,
+            map: Map::clone(&ecs.fetch::<Map>()),
+            entities: Self::capture_entities(ecs)
+                .into_iter()
+                .map(|(_, snapshot)| snapshot)
+                .collect(),
+        }
+    }
+
+    /// Snapshots of the entities along with their ids, in id order.
+    pub(crate) fn capture_entities(ecs: &World) -> Vec<(u32, EntitySnapshot)> {
+        let entities = ecs.entities();
+        let parts = ecs.read_storage::<TrainParts>();
+        let parameters = ecs.read_storage::<TrainParameters>();
+        let colors = ecs.read_storage::<TrainColors>();
+        let routes = ecs.read_storage::<Route>();
+        let owners = ecs.read_storage::<Owner>();
+        let positions = ecs.read_storage::<Point>();
+        let directions = ecs.read_storage::<Direction>();
+        let lights = ecs.read_storage::<TrafficLight>();
+        let overrides = ecs.read_storage::<SignalOverride>();
+        let stations = ecs.read_storage::<Station>();
+
+        let mut snapshots = Vec::new();
+        for e in entities.join() {
+            if let (Some(parts), Some(parameters), Some(colors)) =
+                (parts.get(e), parameters.get(e), colors.get(e))
+            {
+                snapshots.push((
+                    e.id(),
+                    EntitySnapshot::Train {
+                        parts: parts.clone(),
+                        parameters: parameters.clone(),
+                        colors: *colors,
+                        route: routes.get(e).cloned(),
+                        owner: owners.get(e).copied(),
+                    },
+                ));
+            } else if let (Some(&position), Some(&direction), Some(&light)) =
+                (positions.get(e), directions.get(e), lights.get(e))
+            {
+                snapshots.push((
+                    e.id(),
+                    EntitySnapshot::Signal {
+                        position,
+                        direction,
+                        light,
+                        manual: overrides.get(e).copied(),
+                    },
+                ));
+            } else if let (Some(&position), Some(station)) = (positions.get(e), stations.get(e)) {
+                snapshots.push((
+                    e.id(),
+                    EntitySnapshot::Station {
+                        position,
+                        station: station.clone(),
+                    },
+                ));
+            }
+        }
+        snapshots
+    }
+
+    /// Fills an empty world with the snapshot.
+    pub fn restore(&self, ecs: &mut World) {
+        ecs.insert(self.map.clone());
+        ecs.insert(SimClock { tick: self.tick });
+
+        for snapshot in self.entities.iter().cloned() {
+            match snapshot {
+                EntitySnapshot::Train {
+                    parts,
+                    parameters,
+                    colors,
+                    route,
+                    owner,
+                } => {
+                    let head = TrainHead {
+                        position: parts.parts[0].position,
+                    };
+                    let mut builder = ecs
+                        .create_entity()
+                        .with(head)
+                        .with(parts)
+                        .with(parameters)
+                        .with(colors);
+                    if let Some(route) = route {
+                        builder = builder.with(route);
+                    }
+                    if let Some(owner) = owner {
+                        builder = builder.with(owner);
+                    }
+                    builder.build();
+                }
+                EntitySnapshot::Signal {
+                    position,
+                    direction,
+                    light,
+                    manual,
+                } => {
+                    let mut builder = ecs
+                        .create_entity()
+                        .with(position)
+                        .with(direction)
+                        .with(light);
+                    if let Some(manual) = manual {
+                        builder = builder.with(manual);
+                    }
+                    builder.build();
+                }
+                EntitySnapshot::Station { position, station } => {
+                    ecs.create_entity().with(position).with(station).build();
+                }
+            }
+        }
+
+        let map = ecs.fetch::<Map>();
+        let entities = ecs.entities();
+        let parts = ecs.read_storage::<TrainParts>();
+        let mut occupancy = ecs.fetch_mut::<Occupancy>();
+        *occupancy = Occupancy::default();
+        occupancy.fit(&map, (&entities, &parts).join());
+    }
+}
+
+/// A command along with the tick it was given before.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    pub tick: u64,
+    pub command: Command,
+}
+
+/// The starting world of a session and every command given during it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub start: WorldSnapshot,
+    pub inputs: Vec<RecordedInput>,
+    /// How many ticks the session lasted.
+    pub ticks: u64,
+}
+
+impl Recording {
+    pub fn start(ecs: &World) -> Self {
+        Self {
+            start: WorldSnapshot::capture(ecs),
+            inputs: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, command: Command) {
+        self.inputs.push(RecordedInput { tick, command });
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> AppResult<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Tick the recording starts on.
+    pub fn first_tick(&self) -> u64 {
+        self.start.tick
+    }
+
+    /// Tick the recording ends on.
+    pub fn last_tick(&self) -> u64 {
+        self.start.tick + self.ticks
+    }
+}
+
+/// Fastest a replay can run, in ticks per tick of the terminal.
+pub const MAX_REPLAY_SPEED: u32 = 16;
+
+/// State of a recording being played back.
+pub struct Playback {
+    pub recording: Recording,
+    pub paused: bool,
+    /// Ticks simulated on every tick of the terminal.
+    pub speed: u32,
+    /// Index of the first input not applied yet.
+    next_input: usize,
+}
+
+impl Playback {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            paused: false,
+            speed: 1,
+            next_input: 0,
+        }
+    }
+
+    /// Sets the world up as it was at the start of the recording.
+    pub fn rewind(&mut self, ecs: &mut World) {
+        self.recording.start.restore(ecs);
+        self.next_input = 0;
+    }
+
+    /// Applies the inputs given before the current tick.
+    pub fn apply_inputs(&mut self, ecs: &mut World) {
+        let tick = ecs.fetch::<SimClock>().tick;
+        while let Some(input) = self.recording.inputs.get(self.next_input) {
+            if input.tick > tick {
+                break;
+            }
+            input.command.apply(ecs);
+            self.next_input += 1;
+        }
+    }
+
+    pub fn is_finished(&self, ecs: &World) -> bool {
+        ecs.fetch::<SimClock>().tick >= self.recording.last_tick()
+    }
+}
@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use specs::{Entity, World, WorldExt};
+
+use crate::components::{Direction, Point, Route, RouteCost, Station, TrainParameters, TrainParts};
+use crate::map::Map;
+use crate::sim_events::{EventBus, SimEventKind};
+
+/// A train standing on a tile, travelling in a direction.
+type State = (Point, Direction);
+
+struct Candidate {
+    cost: f32,
+    state: State,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Reversed, so that the heap pops the cheapest candidate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Finds the cheapest way for a train to get its head to `destination`, avoiding `blocked` tiles.
+///
+/// The path starts at the current head position and ends at the destination.
+pub fn find_route(
+    map: &Map,
+    parts: &TrainParts,
+    params: &TrainParameters,
+    destination: Point,
+    cost: RouteCost,
+    blocked: &HashSet<Point>,
+) -> Option<Vec<Point>> {
+    let start = (parts.parts.first()?.position, params.movement_direction);
+    let mut best: HashMap<State, f32> = HashMap::from([(start, 0.0)]);
+    let mut came_from: HashMap<State, State> = HashMap::new();
+    let mut queue = BinaryHeap::from([Candidate {
+        cost: 0.0,
+        state: start,
+    }]);
+
+    while let Some(Candidate {
+        cost: so_far,
+        state,
+    }) = queue.pop()
+    {
+        let (pos, dir) = state;
+        if pos == destination {
+            let mut path = vec![pos];
+            let mut state = state;
+            while let Some(&previous) = came_from.get(&state) {
+                path.push(previous.0);
+                state = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if best.get(&state).is_some_and(|&b| so_far > b) {
+            continue;
+        }
+
+        for exit in map.get_tile_at_xy(pos).shape().exits(dir) {
+            let next = pos + Point::from(exit);
+            if !map.can_train_enter(next, exit, parts) || blocked.contains(&next) {
+                continue;
+            }
+            let step = match cost {
+                RouteCost::Shortest => 1.0,
+                RouteCost::Fastest => {
+                    let limit = map.get_tile_at_xy(next).speed_limit();
+                    1.0 / limit.map_or(params.max_speed, |l| l.min(params.max_speed))
+                }
+            };
+
+            let next_state = (next, exit);
+            let next_cost = so_far + step;
+            if best.get(&next_state).is_none_or(|&b| next_cost < b) {
+                best.insert(next_state, next_cost);
+                came_from.insert(next_state, state);
+                queue.push(Candidate {
+                    cost: next_cost,
+                    state: next_state,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+/// Marks the route arrived while the head is at its destination, announcing the arrival once.
+///
+/// Returns `true` if the train has just arrived.
+pub fn update_arrival(
+    route: &mut Route,
+    head: Point,
+    train: Entity,
+    tick: u64,
+    events: &mut EventBus,
+) -> bool {
+    let had_arrived = route.arrived;
+    route.arrived = head == route.destination;
+    let just_arrived = route.arrived && !had_arrived;
+    if just_arrived {
+        events.publish(
+            tick,
+            Some(train),
+            SimEventKind::TrainArrived {
+                destination: route.destination,
+            },
+        );
+    }
+    just_arrived
+}
+
+/// Gives the train a destination tile, the route there is planned on the next tick.
+pub fn send_train(ecs: &mut World, train: Entity, destination: Point, cost: RouteCost) {
+    ecs.write_storage::<Route>()
+        .insert(train, Route::new(destination, cost))
+        .expect("Train entity should be alive");
+}
+
+/// Sends the train to a station, returns `false` if there is no such station.
+pub fn send_train_to_station(
+    ecs: &mut World,
+    train: Entity,
+    station: Entity,
+    cost: RouteCost,
+) -> bool {
+    let position = {
+        let stations = ecs.read_storage::<Station>();
+        let positions = ecs.read_storage::<Point>();
+        if !stations.contains(station) {
+            return false;
+        }
+        positions.get(station).copied()
+    };
+
+    match position {
+        Some(p) => {
+            send_train(ecs, train, p, cost);
+            true
+        }
+        None => false,
+    }
+}
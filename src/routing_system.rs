@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteExpect, WriteStorage};
+
+use crate::components::{Direction, Point, Route, TrainParameters, TrainParts};
+use crate::map::Map;
+use crate::occupancy::Occupancy;
+use crate::routing::{find_route, update_arrival};
+use crate::sim_events::{EventBus, SimClock};
+
+/// How many tiles ahead of a train junctions are set for its route.
+const SWITCHING_DISTANCE: usize = 10;
+/// How many ticks a train waits before looking for a way to an unreachable destination again.
+pub const REPLANNING_DELAY: u64 = 20;
+
+/// Plans routes for trains with a destination, sets junctions in front of them
+/// and finds a way around trains standing in their path.
+pub struct RoutingTrain {}
+
+impl<'a> System<'a> for RoutingTrain {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, TrainParts>,
+        ReadStorage<'a, TrainParameters>,
+        WriteStorage<'a, Route>,
+        WriteExpect<'a, Map>,
+        Read<'a, Occupancy>,
+        Read<'a, SimClock>,
+        Write<'a, EventBus>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, trains, parameters, mut routes, mut map, occupancy, clock, mut events) =
+            data;
+
+        for (e, parts, params, route) in (&entities, &trains, &parameters, &mut routes).join() {
+            let head = parts.parts[0].position;
+            update_arrival(route, head, e, clock.tick, &mut events);
+            if route.arrived {
+                route.path = vec![head];
+                continue;
+            }
+
+            // Forget the part of the path already travelled
+            match route.path.iter().position(|&p| p == head) {
+                Some(i) => {
+                    route.path.drain(..i);
+                }
+                None => route.path.clear(),
+            }
+
+            let in_the_way = |p: &Point| occupancy.train_at(*p).is_some_and(|o| o != e);
+            let replan = route.path.is_empty() || route.path.iter().any(in_the_way);
+            if replan && clock.tick >= route.retry_at {
+                let blocked: HashSet<Point> = occupancy.occupied_by_others(e).collect();
+                let path = find_route(&map, parts, params, route.destination, route.cost, &blocked)
+                    .or_else(|| {
+                        // Nothing goes around, wait for the way to clear instead
+                        find_route(
+                            &map,
+                            parts,
+                            params,
+                            route.destination,
+                            route.cost,
+                            &HashSet::new(),
+                        )
+                    });
+                match path {
+                    Some(path) => route.path = path,
+                    None => route.retry_at = clock.tick + REPLANNING_DELAY,
+                }
+            }
+
+            // Junctions under other trains are left alone
+            let mut dir = params.movement_direction;
+            for step in route.path.windows(2).take(SWITCHING_DISTANCE) {
+                let Some(exit) = Direction::between(step[0], step[1]) else {
+                    break;
+                };
+                if map.get_tile_at_xy(step[0]).shape().is_junction() && !in_the_way(&step[0]) {
+                    map.set_route(step[0], dir, exit);
+                }
+                dir = exit;
+            }
+        }
+    }
+}
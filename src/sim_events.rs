@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use specs::Entity;
+
+use crate::components::Point;
+
+/// How many events the bus keeps before dropping the oldest ones.
+const MAX_EVENTS: usize = 500;
+
+/// Simulation time, counted in ticks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimClock {
+    pub tick: u64,
+}
+
+/// What happened, without the details.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimEventType {
+    SignalChanged,
+    TrainStopped,
+    TrainArrived,
+    Collision,
+    Derailment,
+}
+
+impl SimEventType {
+    pub const ALL: [SimEventType; 5] = [
+        SimEventType::SignalChanged,
+        SimEventType::TrainStopped,
+        SimEventType::TrainArrived,
+        SimEventType::Collision,
+        SimEventType::Derailment,
+    ];
+}
+
+impl fmt::Display for SimEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SimEventType::SignalChanged => "signals",
+            SimEventType::TrainStopped => "stops",
+            SimEventType::TrainArrived => "arrivals",
+            SimEventType::Collision => "collisions",
+            SimEventType::Derailment => "derailments",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SimEventKind {
+    SignalChanged {
+        position: Point,
+        is_green: bool,
+    },
+    TrainStopped {
+        position: Point,
+    },
+    TrainArrived {
+        destination: Point,
+    },
+    /// The train ran into `other` too fast to couple.
+    Collision {
+        position: Point,
+        other: Entity,
+    },
+    /// The train overran the buffer stop at `position`.
+    Derailment {
+        position: Point,
+    },
+}
+
+impl SimEventKind {
+    pub fn event_type(&self) -> SimEventType {
+        match self {
+            SimEventKind::SignalChanged { .. } => SimEventType::SignalChanged,
+            SimEventKind::TrainStopped { .. } => SimEventType::TrainStopped,
+            SimEventKind::TrainArrived { .. } => SimEventType::TrainArrived,
+            SimEventKind::Collision { .. } => SimEventType::Collision,
+            SimEventKind::Derailment { .. } => SimEventType::Derailment,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SimEvent {
+    /// Tick the event happened on.
+    pub tick: u64,
+    /// Train the event is about, if any.
+    pub train: Option<Entity>,
+    pub kind: SimEventKind,
+}
+
+impl fmt::Display for SimEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.tick)?;
+        if let Some(train) = self.train {
+            write!(f, "Train {} ", train.id())?;
+        }
+        match self.kind {
+            SimEventKind::SignalChanged { position, is_green } => write!(
+                f,
+                "Signal at ({}, {}) turned {}",
+                position.x,
+                position.y,
+                if is_green { "green" } else { "red" }
+            ),
+            SimEventKind::TrainStopped { position } => {
+                write!(f, "stopped at ({}, {})", position.x, position.y)
+            }
+            SimEventKind::TrainArrived { destination } => {
+                write!(f, "arrived at ({}, {})", destination.x, destination.y)
+            }
+            SimEventKind::Collision { position, other } => write!(
+                f,
+                "collided with train {} at ({}, {})",
+                other.id(),
+                position.x,
+                position.y
+            ),
+            SimEventKind::Derailment { position } => {
+                write!(f, "derailed at ({}, {})", position.x, position.y)
+            }
+        }
+    }
+}
+
+/// Which events the message feed shows.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct EventFilter {
+    pub event_type: Option<SimEventType>,
+    pub train: Option<Entity>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &SimEvent) -> bool {
+        self.event_type.is_none_or(|t| event.kind.event_type() == t)
+            && self.train.is_none_or(|t| {
+                event.train == Some(t)
+                    || matches!(event.kind, SimEventKind::Collision { other, .. } if other == t)
+            })
+    }
+}
+
+/// Events published by the systems, oldest first.
+#[derive(Default)]
+pub struct EventBus {
+    events: VecDeque<SimEvent>,
+}
+
+impl EventBus {
+    pub fn publish(&mut self, tick: u64, train: Option<Entity>, kind: SimEventKind) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(SimEvent { tick, train, kind });
+    }
+
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &SimEvent> {
+        self.events.iter()
+    }
+
+    /// Events passing the filter, newest first.
+    pub fn latest<'a>(&'a self, filter: &'a EventFilter) -> impl Iterator<Item = &'a SimEvent> {
+        self.events.iter().rev().filter(move |e| filter.matches(e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
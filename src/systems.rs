@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+
+use specs::{Dispatcher, DispatcherBuilder, Read, System, SystemData, World};
+
+use crate::coupling_system::CouplingTrains;
+use crate::driver_system::DrivingTrain;
+use crate::metrics_system::CollectingMetrics;
+use crate::moving_train_system::MovingTrain;
+use crate::routing_system::RoutingTrain;
+use crate::track_graph_system::UpdatingTrackGraph;
+use crate::traction_system::ApplyingTraction;
+use crate::traffic_light_system::ActiveTrafficLight;
+
+pub const UPDATING_TRACK_GRAPH: &str = "updating_track_graph";
+pub const ROUTING_TRAIN: &str = "routing_train";
+pub const DRIVING_TRAIN: &str = "driving_train";
+pub const APPLYING_TRACTION: &str = "applying_traction";
+pub const MOVING_TRAIN: &str = "moving_train";
+pub const COUPLING_TRAINS: &str = "coupling_trains";
+pub const ACTIVE_TRAFFIC_LIGHT: &str = "active_traffic_light";
+pub const COLLECTING_METRICS: &str = "collecting_metrics";
+
+/// Names of the systems that are skipped on every tick.
+#[derive(Default, Debug)]
+pub struct DisabledSystems(pub HashSet<String>);
+
+/// Wraps a system so that it does nothing while its name is in [`DisabledSystems`].
+pub struct Switchable<S> {
+    name: &'static str,
+    system: S,
+}
+
+impl<'a, S> System<'a> for Switchable<S>
+where
+    S: System<'a>,
+    S::SystemData: SystemData<'a>,
+{
+    type SystemData = (Read<'a, DisabledSystems>, S::SystemData);
+
+    fn run(&mut self, (disabled, data): Self::SystemData) {
+        if !disabled.0.contains(self.name) {
+            self.system.run(data);
+        }
+    }
+}
+
+/// Collects the systems run on every tick along with the order between them.
+pub struct SystemsBuilder {
+    dispatcher: DispatcherBuilder<'static, 'static>,
+    names: Vec<&'static str>,
+}
+
+impl Default for SystemsBuilder {
+    fn default() -> Self {
+        Self {
+            dispatcher: DispatcherBuilder::new(),
+            names: Vec::new(),
+        }
+    }
+}
+
+impl SystemsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The systems of the game itself.
+    pub fn standard() -> Self {
+        Self::new()
+            .with(UpdatingTrackGraph {}, UPDATING_TRACK_GRAPH, &[])
+            .with(RoutingTrain {}, ROUTING_TRAIN, &[UPDATING_TRACK_GRAPH])
+            .with(DrivingTrain {}, DRIVING_TRAIN, &[ROUTING_TRAIN])
+            .with(ApplyingTraction {}, APPLYING_TRACTION, &[DRIVING_TRAIN])
+            .with(MovingTrain {}, MOVING_TRAIN, &[APPLYING_TRACTION])
+            .with(CouplingTrains {}, COUPLING_TRAINS, &[MOVING_TRAIN])
+            .with(
+                ActiveTrafficLight {},
+                ACTIVE_TRAFFIC_LIGHT,
+                &[UPDATING_TRACK_GRAPH, COUPLING_TRAINS],
+            )
+            .with(
+                CollectingMetrics {},
+                COLLECTING_METRICS,
+                &[ACTIVE_TRAFFIC_LIGHT],
+            )
+    }
+
+    /// Adds a system that runs after all the systems named in `dependencies`.
+    ///
+    /// Panics if a dependency hasn't been added yet or the name is already taken.
+    pub fn with<S>(mut self, system: S, name: &'static str, dependencies: &[&str]) -> Self
+    where
+        S: for<'a> System<'a> + Send + 'static,
+        for<'a> <S as System<'a>>::SystemData: SystemData<'a>,
+    {
+        self.add(system, name, dependencies);
+        self
+    }
+
+    /// Same as [`SystemsBuilder::with`], for builders held in a variable.
+    pub fn add<S>(&mut self, system: S, name: &'static str, dependencies: &[&str])
+    where
+        S: for<'a> System<'a> + Send + 'static,
+        for<'a> <S as System<'a>>::SystemData: SystemData<'a>,
+    {
+        assert!(
+            !self.names.contains(&name),
+            "A system named {} is already registered",
+            name
+        );
+        self.dispatcher
+            .add(Switchable { name, system }, name, dependencies);
+        self.names.push(name);
+    }
+
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    /// Builds the dispatcher and sets up the resources and storages its systems need.
+    pub fn build(self, ecs: &mut World) -> Dispatcher<'static, 'static> {
+        let mut dispatcher = self.dispatcher.build();
+        dispatcher.setup(ecs);
+        dispatcher
+    }
+}
@@ -0,0 +1,188 @@
+use std::fs;
+use std::path::Path;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppResult;
+use crate::sim_events::SimEventType;
+
+/// Directory in the config directory the theme files are read from.
+pub const THEMES_DIR: &str = "themes";
+
+/// Colours the game is drawn in.
+///
+/// Theme files are JSON, any colour missing from a file is taken from the default theme.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    /// Borders and titles of the panels.
+    pub border: Color,
+    pub text: Color,
+    pub track: Color,
+    /// Background of the tiles in a block behind a red signal, and under trains.
+    pub occupied: Color,
+    /// Background of station tiles.
+    pub station: Color,
+    pub signal_green: Color,
+    pub signal_red: Color,
+    /// Colour of flat track in the gradient overlay, blended into `steep_track` as it gets steeper.
+    pub flat_track: Color,
+    pub steep_track: Color,
+    /// Draws every train in this colour instead of its own.
+    pub trains: Option<Color>,
+    pub signal_event: Color,
+    pub stop_event: Color,
+    pub arrival_event: Color,
+    pub crash_event: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            background: Color::Black,
+            border: Color::Cyan,
+            text: Color::White,
+            track: Color::Rgb(128, 128, 128),
+            occupied: Color::Rgb(64, 0, 0),
+            station: Color::Rgb(0, 0, 96),
+            signal_green: Color::LightGreen,
+            signal_red: Color::LightRed,
+            flat_track: Color::Rgb(0, 255, 0),
+            steep_track: Color::Rgb(255, 0, 0),
+            trains: None,
+            signal_event: Color::Yellow,
+            stop_event: Color::White,
+            arrival_event: Color::LightGreen,
+            crash_event: Color::LightRed,
+        }
+    }
+}
+
+impl Theme {
+    /// Bright colours on black, for low contrast screens and bright rooms.
+    pub fn high_contrast() -> Self {
+        Self {
+            name: String::from("high-contrast"),
+            border: Color::White,
+            track: Color::White,
+            occupied: Color::Rgb(128, 0, 0),
+            station: Color::Rgb(0, 0, 192),
+            signal_green: Color::Rgb(0, 255, 0),
+            signal_red: Color::Rgb(255, 0, 0),
+            signal_event: Color::Rgb(255, 255, 0),
+            arrival_event: Color::Rgb(0, 255, 0),
+            crash_event: Color::Rgb(255, 0, 0),
+            ..Self::default()
+        }
+    }
+
+    /// Blue and orange instead of green and red, told apart with red-green colour blindness.
+    pub fn deuteranopia() -> Self {
+        let blue = Color::Rgb(0, 114, 178);
+        let orange = Color::Rgb(230, 159, 0);
+        Self {
+            name: String::from("deuteranopia"),
+            occupied: Color::Rgb(80, 48, 0),
+            station: Color::Rgb(0, 40, 80),
+            signal_green: blue,
+            signal_red: orange,
+            flat_track: blue,
+            steep_track: orange,
+            arrival_event: blue,
+            crash_event: orange,
+            ..Self::default()
+        }
+    }
+
+    /// Shades of grey only, signals are told apart by their glyphs.
+    pub fn monochrome() -> Self {
+        Self {
+            name: String::from("monochrome"),
+            border: Color::White,
+            track: Color::Gray,
+            occupied: Color::Rgb(64, 64, 64),
+            station: Color::Rgb(32, 32, 32),
+            signal_green: Color::White,
+            signal_red: Color::White,
+            flat_track: Color::Rgb(96, 96, 96),
+            steep_track: Color::Rgb(255, 255, 255),
+            trains: Some(Color::White),
+            signal_event: Color::White,
+            stop_event: Color::Gray,
+            arrival_event: Color::White,
+            crash_event: Color::White,
+            ..Self::default()
+        }
+    }
+
+    /// The themes that come with the game.
+    pub fn builtin() -> Vec<Theme> {
+        vec![
+            Theme::default(),
+            Theme::high_contrast(),
+            Theme::deuteranopia(),
+            Theme::monochrome(),
+        ]
+    }
+
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let mut theme: Theme = serde_json::from_str(&fs::read_to_string(path)?)?;
+        // A theme without a name is called after its file
+        if theme.name == Theme::default().name {
+            if let Some(stem) = path.file_stem() {
+                theme.name = stem.to_string_lossy().into_owned();
+            }
+        }
+        Ok(theme)
+    }
+
+    /// The themes in the `.json` files of a directory, sorted by file name.
+    pub fn load_dir(dir: &Path) -> AppResult<Vec<Theme>> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        paths.retain(|p| p.extension().is_some_and(|e| e == "json"));
+        paths.sort();
+        paths.iter().map(|p| Theme::load(p)).collect()
+    }
+
+    pub fn signal(&self, is_green: bool) -> Color {
+        if is_green {
+            self.signal_green
+        } else {
+            self.signal_red
+        }
+    }
+
+    pub fn event(&self, event_type: SimEventType) -> Color {
+        match event_type {
+            SimEventType::SignalChanged => self.signal_event,
+            SimEventType::TrainStopped => self.stop_event,
+            SimEventType::TrainArrived => self.arrival_event,
+            SimEventType::Collision | SimEventType::Derailment => self.crash_event,
+        }
+    }
+
+    /// Colour of track on a gradient, `t` going from 0 for flat to 1 for steep.
+    pub fn gradient(&self, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        match (self.flat_track, self.steep_track) {
+            (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+                Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            }
+            // Only RGB colours can be blended
+            (flat, steep) => {
+                if t < 0.5 {
+                    flat
+                } else {
+                    steep
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::components::{Direction, Point};
+use crate::map::Map;
+
+pub type NodeId = usize;
+pub type EdgeId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A tile where the track branches.
+    Junction,
+    /// A buffer stop.
+    End,
+    /// A tile with a traffic light on it.
+    Signal,
+    /// An arbitrary tile of a loop which has no other nodes on it.
+    Loop,
+}
+
+#[derive(Clone, Debug)]
+pub struct TrackNode {
+    pub position: Point,
+    pub kind: NodeKind,
+    /// Edges leaving the node, along with the side of the tile they leave through.
+    pub edges: Vec<(Direction, EdgeId)>,
+}
+
+/// A stretch of plain track between two nodes.
+#[derive(Clone, Debug)]
+pub struct TrackEdge {
+    pub from: NodeId,
+    /// Side of the `from` tile the edge leaves through.
+    pub from_side: Direction,
+    /// `None` if the track runs into nothing.
+    pub to: Option<NodeId>,
+    /// Side of the `to` tile the edge comes in through.
+    pub to_side: Direction,
+    /// Tiles between the two nodes, starting next to `from`.
+    pub tiles: Vec<Point>,
+}
+
+impl TrackEdge {
+    /// Number of steps from one node to the other.
+    pub fn length(&self) -> usize {
+        self.tiles.len() + 1
+    }
+
+    /// The node at the other end of the edge and the side it is reached through.
+    pub fn other_end(&self, node: NodeId, side: Direction) -> Option<(NodeId, Direction)> {
+        if node == self.from && side == self.from_side {
+            self.to.map(|to| (to, self.to_side))
+        } else {
+            Some((self.from, self.from_side))
+        }
+    }
+}
+
+/// The track network compiled from the tile grid into nodes at junctions,
+/// buffer stops and signals, linked by edges of plain track.
+#[derive(Default)]
+pub struct TrackGraph {
+    nodes: Vec<TrackNode>,
+    edges: Vec<TrackEdge>,
+    node_positions: HashMap<Point, NodeId>,
+    signals: Vec<(Point, Direction)>,
+    map_version: Option<u64>,
+}
+
+impl TrackGraph {
+    pub fn build(map: &Map, signals: &[(Point, Direction)]) -> Self {
+        let mut graph = Self {
+            signals: sorted(signals),
+            map_version: Some(map.version()),
+            ..Self::default()
+        };
+
+        for y in 0..map.h() {
+            for x in 0..map.w() {
+                let position = Point::new(x, y);
+                let shape = map.get_tile_at_xy(position).shape();
+                let kind = match shape.connections().len() {
+                    0 => continue,
+                    1 => NodeKind::End,
+                    3 => NodeKind::Junction,
+                    _ if graph.has_signal_at(position) => NodeKind::Signal,
+                    _ => continue,
+                };
+                graph.add_node(position, kind);
+            }
+        }
+
+        for node in 0..graph.nodes.len() {
+            graph.trace_edges(map, node);
+        }
+
+        // Loops without any nodes on them get one on an arbitrary tile
+        let mut covered: HashSet<Point> = graph.node_positions.keys().copied().collect();
+        covered.extend(graph.edges.iter().flat_map(|e| e.tiles.iter().copied()));
+        for y in 0..map.h() {
+            for x in 0..map.w() {
+                let position = Point::new(x, y);
+                if covered.contains(&position)
+                    || map.get_tile_at_xy(position).shape().connections().len() != 2
+                {
+                    continue;
+                }
+                let node = graph.add_node(position, NodeKind::Loop);
+                let first_edge = graph.edges.len();
+                graph.trace_edges(map, node);
+                covered.insert(position);
+                covered.extend(
+                    graph.edges[first_edge..]
+                        .iter()
+                        .flat_map(|e| e.tiles.iter().copied()),
+                );
+            }
+        }
+
+        graph
+    }
+
+    fn add_node(&mut self, position: Point, kind: NodeKind) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(TrackNode {
+            position,
+            kind,
+            edges: Vec::new(),
+        });
+        self.node_positions.insert(position, id);
+        id
+    }
+
+    /// Follows the track out of every side of the node that has no edge yet.
+    fn trace_edges(&mut self, map: &Map, node: NodeId) {
+        let start = self.nodes[node].position;
+        for &side in map.get_tile_at_xy(start).shape().connections() {
+            if self.nodes[node].edges.iter().any(|&(s, _)| s == side) {
+                continue;
+            }
+
+            let mut tiles = Vec::new();
+            let mut pos = start;
+            let mut dir = side;
+            let to = loop {
+                let next = pos + Point::from(dir);
+                if !map.can_enter(next, dir) {
+                    break None;
+                }
+                if let Some(&to) = self.node_positions.get(&next) {
+                    break Some(to);
+                }
+                tiles.push(next);
+                pos = next;
+                dir = pass_through(map, pos, dir);
+            };
+
+            let id = self.edges.len();
+            let to_side = if to.is_some() { dir.opposite() } else { dir };
+            self.edges.push(TrackEdge {
+                from: node,
+                from_side: side,
+                to,
+                to_side,
+                tiles,
+            });
+            self.nodes[node].edges.push((side, id));
+            if let Some(to) = to {
+                self.nodes[to].edges.push((to_side, id));
+            }
+        }
+    }
+
+    pub fn nodes(&self) -> &[TrackNode] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[TrackEdge] {
+        &self.edges
+    }
+
+    pub fn node(&self, id: NodeId) -> &TrackNode {
+        &self.nodes[id]
+    }
+
+    pub fn edge(&self, id: EdgeId) -> &TrackEdge {
+        &self.edges[id]
+    }
+
+    pub fn node_at(&self, position: Point) -> Option<NodeId> {
+        self.node_positions.get(&position).copied()
+    }
+
+    /// Edge leaving `node` through `side` of its tile.
+    pub fn edge_from(&self, node: NodeId, side: Direction) -> Option<EdgeId> {
+        self.nodes[node]
+            .edges
+            .iter()
+            .find(|&&(s, _)| s == side)
+            .map(|&(_, e)| e)
+    }
+
+    fn has_signal_at(&self, position: Point) -> bool {
+        self.signals.iter().any(|&(p, _)| p == position)
+    }
+
+    /// Whether a signal at `position` faces along the line running out of `side`.
+    fn has_signal_along(&self, position: Point, side: Direction) -> bool {
+        self.signals
+            .iter()
+            .any(|&(p, d)| p == position && (d == side || d == side.opposite()))
+    }
+
+    /// Whether the graph was built from this version of the map and these signals.
+    pub fn is_up_to_date(&self, map: &Map, signals: &[(Point, Direction)]) -> bool {
+        self.map_version == Some(map.version()) && self.signals == sorted(signals)
+    }
+
+    /// Tiles protected by a signal at `position` facing `direction`:
+    /// all track reachable from it without passing another signal.
+    pub fn block_from(&self, position: Point, direction: Direction) -> Vec<Point> {
+        let mut block = Vec::new();
+        let Some(start) = self.node_at(position) else {
+            return block;
+        };
+        let Some(first_edge) = self.edge_from(start, direction) else {
+            return block;
+        };
+
+        let mut visited_edges: HashSet<EdgeId> = HashSet::new();
+        let mut visited_nodes: HashSet<NodeId> = HashSet::from([start]);
+        let mut queue = vec![(first_edge, start, direction)];
+
+        while let Some((edge_id, from, side)) = queue.pop() {
+            if !visited_edges.insert(edge_id) {
+                continue;
+            }
+            let edge = &self.edges[edge_id];
+            let Some((to, to_side)) = edge.other_end(from, side) else {
+                block.extend(edge.tiles.iter().copied());
+                continue;
+            };
+            // Edges are stored in one direction only, walk them the way we came in
+            if edge.from == from && edge.from_side == side {
+                block.extend(edge.tiles.iter().copied());
+            } else {
+                block.extend(edge.tiles.iter().rev().copied());
+            }
+
+            let node = &self.nodes[to];
+            if node.edges.len() == 4 {
+                // Crossings are passed straight through, a signal on one only ends the line it faces along
+                let exit = to_side.opposite();
+                if self.has_signal_along(node.position, exit) {
+                    continue;
+                }
+                if visited_nodes.insert(to) {
+                    block.push(node.position);
+                }
+                if let Some(e) = self.edge_from(to, exit) {
+                    queue.push((e, to, exit));
+                }
+                continue;
+            }
+            if self.has_signal_at(node.position) || !visited_nodes.insert(to) {
+                continue;
+            }
+            block.push(node.position);
+            for &(s, e) in node.edges.iter().filter(|&&(s, _)| s != to_side) {
+                queue.push((e, to, s));
+            }
+        }
+
+        block
+    }
+}
+
+/// Side a train entering `pos` in `direction` leaves through, ignoring junctions.
+fn pass_through(map: &Map, pos: Point, direction: Direction) -> Direction {
+    let shape = map.get_tile_at_xy(pos).shape();
+    shape.exits(direction).first().copied().unwrap_or(direction)
+}
+
+fn sorted(signals: &[(Point, Direction)]) -> Vec<(Point, Direction)> {
+    let mut signals = signals.to_vec();
+    signals.sort_by_key(|&(p, d)| (p.y, p.x, d as u8));
+    signals
+}
@@ -0,0 +1,31 @@
+use specs::{Join, ReadExpect, ReadStorage, System, Write};
+
+use crate::components::{Direction, Point, TrafficLight};
+use crate::map::Map;
+use crate::track_graph::TrackGraph;
+
+/// Rebuilds the track graph whenever the track or the signals on it change.
+pub struct UpdatingTrackGraph {}
+
+impl<'a> System<'a> for UpdatingTrackGraph {
+    type SystemData = (
+        Write<'a, TrackGraph>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Point>,
+        ReadStorage<'a, Direction>,
+        ReadStorage<'a, TrafficLight>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut graph, map, tl_positions, tl_directions, lights) = data;
+
+        let signals: Vec<(Point, Direction)> = (&tl_positions, &tl_directions, &lights)
+            .join()
+            .map(|(&p, &d, _)| (p, d))
+            .collect();
+
+        if !graph.is_up_to_date(&map, &signals) {
+            *graph = TrackGraph::build(&map, &signals);
+        }
+    }
+}
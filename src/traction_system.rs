@@ -0,0 +1,53 @@
+use specs::{Join, ReadExpect, System, WriteStorage};
+
+use crate::components::{Traction, TrainParameters, TrainParts, VehicleKind, STEAM_WATER_CAPACITY};
+use crate::map::Map;
+
+/// Water a steam locomotive uses per tick at full throttle.
+const STEAM_WATER_USE: f32 = 0.5;
+/// Water a steam locomotive takes on per tick while standing at a water supply.
+const WATER_REFILL_RATE: f32 = 10.0;
+
+/// Works out the force every locomotive of a train pulls with this tick
+/// and keeps track of the water in steam locomotives.
+pub struct ApplyingTraction {}
+
+impl<'a> System<'a> for ApplyingTraction {
+    type SystemData = (
+        WriteStorage<'a, TrainParts>,
+        WriteStorage<'a, TrainParameters>,
+        ReadExpect<'a, Map>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut parts, mut parameters, map) = data;
+
+        for (parts, params) in (&mut parts, &mut parameters).join() {
+            let mut force = 0.0;
+            let at_water_supply = params.velocity == 0.0 && map.reaches_water_supply(parts);
+
+            for part in parts.parts.iter_mut() {
+                let tile = map.get_tile_at_xy(part.position);
+                let VehicleKind::Locomotive {
+                    traction,
+                    tractive_force,
+                } = &mut part.vehicle.kind
+                else {
+                    continue;
+                };
+
+                force += traction.force(*tractive_force, params.velocity, tile.is_electrified());
+
+                if let Traction::Steam { water } = traction {
+                    *water = if at_water_supply {
+                        (*water + WATER_REFILL_RATE).min(STEAM_WATER_CAPACITY)
+                    } else {
+                        (*water - STEAM_WATER_USE * params.throttle).max(0.0)
+                    };
+                }
+            }
+
+            params.force = force;
+        }
+    }
+}
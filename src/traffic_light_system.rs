@@ -1,52 +1,63 @@
-use crate::components::{Direction, Point, TrafficLight, TrainParts};
+use crate::components::{Direction, Point, SignalOverride, TrafficLight};
 use crate::map::Map;
-use specs::{Entity, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
+use crate::occupancy::Occupancy;
+use crate::sim_events::{EventBus, SimClock, SimEventKind};
+use crate::track_graph::TrackGraph;
+use specs::{Join, Read, ReadStorage, System, Write, WriteExpect, WriteStorage};
 
 pub struct ActiveTrafficLight {}
 
 impl<'a> System<'a> for ActiveTrafficLight {
     type SystemData = (
         WriteExpect<'a, Map>,
+        Read<'a, TrackGraph>,
         ReadStorage<'a, Point>,
         ReadStorage<'a, Direction>,
-        ReadStorage<'a, TrainParts>,
+        Read<'a, Occupancy>,
+        ReadStorage<'a, SignalOverride>,
         WriteStorage<'a, TrafficLight>,
+        Read<'a, SimClock>,
+        Write<'a, EventBus>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, tl_positions, tl_direction, trains, mut lights) = data;
+        let (
+            mut map,
+            graph,
+            tl_positions,
+            tl_direction,
+            occupancy,
+            overrides,
+            mut lights,
+            clock,
+            mut events,
+        ) = data;
 
-        let mut tl_points: Vec<Point> = Vec::new();
-        let mut train_positions: Vec<Point> = Vec::new();
-
-        tl_positions
-            .join()
-            .for_each(|point: &Point| tl_points.push(*point));
-
-        trains.join().for_each(|t| {
-            t.parts.iter().for_each(|p| {
-                train_positions.push(*p);
-            })
-        });
-
-        for (tl_p, tl_d, light) in (&tl_positions, &tl_direction, &mut lights).join() {
-            //  Generate the responsibility zone for light
-
-            let mut responsibility_zone: Vec<Point> = Vec::new();
-            let mut dir = *tl_d;
-            let mut cur_pos = *tl_p + Point::from(dir);
-
-            while !tl_points.contains(&cur_pos) {
-                responsibility_zone.push(cur_pos);
-                dir = map.get_next_direction(cur_pos, dir);
-                cur_pos += Point::from(dir);
-            }
+        for (tl_p, tl_d, manual, light) in
+            (&tl_positions, &tl_direction, overrides.maybe(), &mut lights).join()
+        {
+            //  The responsibility zone of a light is the block of track behind it
+            let responsibility_zone = graph.block_from(*tl_p, *tl_d);
 
             // If  train in responsibility zone set light red
             // Else set light green
-            light.is_green = !responsibility_zone
-                .iter()
-                .any(|p| train_positions.contains(p));
+            let was_green = light.is_green;
+            light.is_green = match manual {
+                Some(manual) => manual.is_green,
+                None => !responsibility_zone
+                    .iter()
+                    .any(|p| occupancy.is_occupied(*p)),
+            };
+            if light.is_green != was_green {
+                events.publish(
+                    clock.tick,
+                    None,
+                    SimEventKind::SignalChanged {
+                        position: *tl_p,
+                        is_green: light.is_green,
+                    },
+                );
+            }
 
             responsibility_zone.iter().for_each(|p| {
                 let i = map.xy_idx(*p);
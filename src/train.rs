@@ -0,0 +1,178 @@
+use specs::{Builder, Entity, Join, World, WorldExt};
+
+use crate::components::{
+    Direction, Owner, Point, TrainColors, TrainHead, TrainParameters, TrainParts, Vehicle,
+};
+use crate::map::Map;
+use crate::occupancy::Occupancy;
+
+/// Creates a standing train from its consist, the head being the first part.
+pub fn spawn_train(
+    ecs: &mut World,
+    parts: TrainParts,
+    movement_direction: Direction,
+    colors: TrainColors,
+) -> Entity {
+    let head = TrainHead {
+        position: parts.parts[0].position,
+    };
+    let parameters = TrainParameters::new(&parts, movement_direction);
+
+    let train = ecs
+        .create_entity()
+        .with(head)
+        .with(parts)
+        .with(colors)
+        .with(parameters)
+        .build();
+    occupy(ecs, train);
+    train
+}
+
+/// Marks the tiles under the train in the occupancy grid.
+fn occupy(ecs: &mut World, train: Entity) {
+    ecs.entry::<Occupancy>().or_insert_with(Occupancy::default);
+
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let parts = ecs.read_storage::<TrainParts>();
+    let mut occupancy = ecs.fetch_mut::<Occupancy>();
+    occupancy.fit(&map, (&entities, &parts).join());
+    if let Some(parts) = parts.get(train) {
+        occupancy.place(train, parts);
+    }
+}
+
+/// Lays out `vehicles` along the track behind a head at `head` travelling in `direction`.
+///
+/// Returns `None` if the track behind the head is too short for the whole train.
+pub fn place_train(
+    map: &Map,
+    head: Point,
+    direction: Direction,
+    vehicles: &[Vehicle],
+) -> Option<TrainParts> {
+    if !map.can_enter(head, direction) {
+        return None;
+    }
+
+    let mut positions = vec![head];
+    let mut pos = head;
+    let mut dir = direction;
+    while positions.len() < vehicles.len() {
+        let behind = pos + Point::from(dir.opposite());
+        if !map.in_bounds(behind) {
+            return None;
+        }
+        dir = map.get_tile_at_xy(behind).shape().entry_for_exit(dir)?;
+        if !map.can_enter(behind, dir) || positions.contains(&behind) {
+            return None;
+        }
+        positions.push(behind);
+        pos = behind;
+    }
+
+    Some(TrainParts::new(&positions, vehicles))
+}
+
+/// Direction the train travels in on its head tile, judging by the positions of its first two cars.
+pub fn heading(parts: &TrainParts) -> Option<Direction> {
+    match parts.parts.as_slice() {
+        [first, second, ..] => Direction::between(second.position, first.position),
+        _ => None,
+    }
+}
+
+/// Turns a standing train around so that its last car becomes the head.
+///
+/// Returns `false` if the train is still moving.
+pub fn reverse_train(ecs: &mut World, train: Entity) -> bool {
+    if turn_around(ecs, train) {
+        occupy(ecs, train);
+        true
+    } else {
+        false
+    }
+}
+
+fn turn_around(ecs: &mut World, train: Entity) -> bool {
+    let map = ecs.fetch::<Map>();
+    let mut heads = ecs.write_storage::<TrainHead>();
+    let mut parts = ecs.write_storage::<TrainParts>();
+    let mut parameters = ecs.write_storage::<TrainParameters>();
+
+    let (Some(head), Some(parts), Some(params)) = (
+        heads.get_mut(train),
+        parts.get_mut(train),
+        parameters.get_mut(train),
+    ) else {
+        return false;
+    };
+    if params.velocity > 0.0 {
+        return false;
+    }
+
+    parts.parts.reverse();
+    head.position = parts.parts[0].position;
+    // A single car leaves through the side it came in from
+    let back = params.movement_direction.opposite();
+    params.movement_direction = heading(parts)
+        .or_else(|| {
+            map.get_tile_at_xy(head.position)
+                .shape()
+                .entry_for_exit(back)
+        })
+        .unwrap_or(back);
+
+    true
+}
+
+/// Splits the train in front of the car with index `at`.
+///
+/// The cut-off cars become a new train rolling on with the same velocity and owner,
+/// which is returned. Returns `None` if there is nothing to split off.
+pub fn uncouple_train(ecs: &mut World, train: Entity, at: usize) -> Option<Entity> {
+    let (tail, direction, velocity, colors) = {
+        let map = ecs.fetch::<Map>();
+        let mut parts = ecs.write_storage::<TrainParts>();
+        let mut parameters = ecs.write_storage::<TrainParameters>();
+        let colors = ecs.read_storage::<TrainColors>();
+
+        // Everything is looked up before the consist is split, so a missing piece changes nothing
+        let parts = parts.get_mut(train)?;
+        let params = parameters.get_mut(train)?;
+        let colors = *colors.get(train)?;
+        if at == 0 || at >= parts.parts.len() {
+            return None;
+        }
+
+        let tail = TrainParts {
+            parts: parts.parts.split_off(at),
+        };
+        params.update_from_consist(parts);
+
+        // A single car keeps following the rest of the train
+        let tail_head = tail.parts[0].position;
+        let front = parts.parts[at - 1].position;
+        let direction = heading(&tail)
+            .or_else(|| {
+                Direction::between(tail_head, front)
+                    .and_then(|exit| map.get_tile_at_xy(tail_head).shape().entry_for_exit(exit))
+            })
+            .unwrap_or(params.movement_direction);
+
+        (tail, direction, params.velocity, colors)
+    };
+
+    let new_train = spawn_train(ecs, tail, direction, colors);
+    if let Some(params) = ecs.write_storage::<TrainParameters>().get_mut(new_train) {
+        params.velocity = velocity;
+    }
+    let owner = ecs.read_storage::<Owner>().get(train).copied();
+    if let Some(owner) = owner {
+        ecs.write_storage::<Owner>()
+            .insert(new_train, owner)
+            .expect("The train was just spawned");
+    }
+    Some(new_train)
+}
@@ -4,6 +4,7 @@ use crate::ui;
 use ratatui::backend::Backend;
 use ratatui::crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::Rect;
 use ratatui::Terminal;
 use std::io;
 use std::panic;
@@ -55,6 +56,12 @@ impl<B: Backend> Tui<B> {
         Ok(())
     }
 
+    /// Fits the interface to the new size of the terminal, redrawing it from scratch.
+    pub fn resize(&mut self, width: u16, height: u16) -> AppResult<()> {
+        self.terminal.resize(Rect::new(0, 0, width, height))?;
+        Ok(())
+    }
+
     /// Resets the terminal interface.
     ///
     /// This function is also used for the panic hook to revert
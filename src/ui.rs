@@ -1,28 +1,40 @@
 use ratatui::text::{Line, Span};
 use ratatui::{
-    layout::Alignment,
-    style::{Color, Style},
-    widgets::{Block, BorderType, Paragraph},
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
+    style::{Modifier, Style},
+    symbols::Marker,
+    widgets::canvas::{Canvas, Context, Line as CanvasLine, Points, Rectangle},
+    widgets::{Block, Clear, Paragraph, Wrap},
     Frame,
 };
 use specs::{Join, World, WorldExt};
 
-use crate::app::App;
-use crate::components::{Direction, Point, TrafficLight, TrainColors, TrainHead, TrainParts};
+use crate::app::{App, MapOverlay};
+use crate::components::{Direction, Point, Station, TrafficLight, TrainColors, TrainParts};
+use crate::glyphs::GlyphSet;
+use crate::keymap::{KeyAction, KeyBindings};
 use crate::map::Map;
+use crate::occupancy::Occupancy;
+use crate::sim_events::{EventBus, EventFilter, SimClock};
+use crate::theme::Theme;
 
-/// Renders the user interface widgets.
+/// Gradient at which the overlay shows the track in the colour of steep track.
+const STEEP_GRADIENT: f32 = 0.2;
 
-fn render_map(ecs: &World) -> Paragraph {
+/// Draws the track, stations, signals and trains, one character per tile.
+pub fn render_map(ecs: &World, overlay: MapOverlay) -> Paragraph<'_> {
     let map = ecs.fetch::<Map>();
+    let theme = ecs.fetch::<Theme>();
+    let glyphs = ecs.fetch::<GlyphSet>();
 
+    let occupancy = ecs.fetch::<Occupancy>();
     let train_parts = ecs.read_storage::<TrainParts>();
-    let train_heads = ecs.read_storage::<TrainHead>();
     let train_colors = ecs.read_storage::<TrainColors>();
 
     let positions = ecs.read_storage::<Point>();
     let directions = ecs.read_storage::<Direction>();
     let traffic_lights = ecs.read_storage::<TrafficLight>();
+    let stations = ecs.read_storage::<Station>();
 
     let mut spans: Vec<Span> = Vec::new();
 
@@ -31,51 +43,71 @@ fn render_map(ecs: &World) -> Paragraph {
             let point = Point::new(x, y);
             let tile = map.get_tile_at_xy(point);
             let i = map.xy_idx(point);
+            let fg = match overlay {
+                MapOverlay::Gradient if !tile.shape().connections().is_empty() => {
+                    theme.gradient(map.steepness(point) / STEEP_GRADIENT)
+                }
+                _ => theme.track,
+            };
             spans.push(Span::styled(
-                String::from(char::from(tile)),
-                Style::default()
-                    .fg(Color::from(tile))
-                    .bg(if map.occupied_tiles[i] {
-                        Color::Rgb(64, 0, 0)
-                    } else {
-                        Color::Black
-                    }),
+                String::from(glyphs.track(tile.shape())),
+                Style::default().fg(fg).bg(if map.occupied_tiles[i] {
+                    theme.occupied
+                } else {
+                    theme.background
+                }),
             ))
         }
     }
 
+    (&positions, &stations).join().for_each(|(&p, _)| {
+        let i = map.xy_idx(p);
+        spans[i].style = spans[i].style.bg(theme.station);
+    });
+
     (&positions, &directions, &traffic_lights)
         .join()
         .for_each(|(&p, &d, t)| {
             let i = map.xy_idx(p);
             spans[i] = Span::styled(
-                String::from(char::from(d)),
-                Style::default().fg(if t.is_green {
-                    Color::LightGreen
-                } else {
-                    Color::LightRed
-                }),
+                String::from(glyphs.signal(d, t.is_green)),
+                Style::default().fg(theme.signal(t.is_green)),
             )
         });
 
-    (&train_parts, &train_heads, &train_colors)
-        .join()
-        .for_each(|(t, h, c)| {
-            t.parts.iter().for_each(|&p| {
-                let i = map.xy_idx(p);
-                let tile = map.get_tile_at_xy(p);
-                spans[i] = Span::styled(
-                    String::from(tile.get_train_char()),
-                    Style::default()
-                        .fg(if p == h.position {
-                            c.head_color
-                        } else {
-                            c.main_color
-                        })
-                        .bg(Color::Rgb(64, 0, 0)),
-                )
-            })
-        });
+    for y in 0..map.h() {
+        for x in 0..map.w() {
+            let p = Point::new(x, y);
+            let Some(occupant) = occupancy.get(p) else {
+                continue;
+            };
+            let (Some(parts), Some(c)) = (
+                train_parts.get(occupant.train),
+                train_colors.get(occupant.train),
+            ) else {
+                continue;
+            };
+            let Some(part) = parts.parts.get(occupant.car) else {
+                continue;
+            };
+            let i = map.xy_idx(p);
+            let shape = map.get_tile_at_xy(p).shape();
+            let glyph = if part.vehicle.is_locomotive() {
+                glyphs.train(shape)
+            } else {
+                glyphs.wagon(shape)
+            };
+            let fg = match theme.trains {
+                Some(color) => color,
+                None if occupant.car == 0 => c.head_color,
+                None => c.main_color,
+            };
+            spans[i] = Span::styled(
+                String::from(glyph),
+                Style::default().fg(fg).bg(theme.occupied),
+            )
+        }
+    }
 
     // All rendering happens up to this point
     let mut lines: Vec<Line> = Vec::with_capacity(map.h() as usize);
@@ -86,21 +118,326 @@ fn render_map(ecs: &World) -> Paragraph {
     Paragraph::new(lines)
 }
 
+/// Width of the message feed panel, borders included.
+const FEED_WIDTH: u16 = 40;
+
+/// Lists the events passing the feed filter, newest first.
+pub fn render_feed(ecs: &World, filter: &EventFilter, scroll: usize) -> Paragraph<'static> {
+    let events = ecs.fetch::<EventBus>();
+    let theme = ecs.fetch::<Theme>();
+    let glyphs = ecs.fetch::<GlyphSet>();
+    let lines: Vec<Line> = events
+        .latest(filter)
+        .skip(scroll)
+        .map(|e| {
+            Line::styled(
+                e.to_string(),
+                Style::default().fg(theme.event(e.kind.event_type())),
+            )
+        })
+        .collect();
+
+    let mut title = String::from("Events");
+    let mut shown: Vec<String> = Vec::new();
+    if let Some(t) = filter.event_type {
+        shown.push(t.to_string());
+    }
+    if let Some(train) = filter.train {
+        shown.push(format!("train {}", train.id()));
+    }
+    if !shown.is_empty() {
+        title = format!("{} ({})", title, shown.join(", "));
+    }
+
+    Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .block(Block::bordered().title(title).border_set(glyphs.border()))
+        .style(Style::default().fg(theme.border).bg(theme.background))
+}
+
+/// Smallest terminal the game is drawn in, anything smaller gets a message instead.
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 5;
+/// Narrowest the map panel gets before the message feed is hidden to make room, unless the
+/// whole map fits in less.
+const MIN_MAP_PANEL_WIDTH: u16 = 32;
+
+/// Start of the part of a `map_len` long axis shown in `view_len` cells, centred on `focus`
+/// where there is room to.
+fn scroll_offset(map_len: i32, view_len: i32, focus: Option<i32>) -> i32 {
+    if map_len <= view_len {
+        return 0;
+    }
+    let focus = focus.unwrap_or(map_len / 2);
+    (focus - view_len / 2).clamp(0, map_len - view_len)
+}
+
+/// Tiles of the map shown in a panel with the given inside, following the cursor or showing
+/// the middle of the map when there is none.
+pub fn viewport(map: &Map, cursor: Option<Point>, inner: Rect) -> Rect {
+    let (width, height) = (inner.width as i32, inner.height as i32);
+    Rect::new(
+        scroll_offset(map.w(), width, cursor.map(|c| c.x)) as u16,
+        scroll_offset(map.h(), height, cursor.map(|c| c.y)) as u16,
+        width.min(map.w()) as u16,
+        height.min(map.h()) as u16,
+    )
+}
+
+/// Cell of the map panel's inside showing a tile, if it is in view. The map is centred
+/// horizontally when it is narrower than the panel.
+fn screen_position(inner: Rect, view: Rect, tile: Point) -> Option<(u16, u16)> {
+    let x = tile.x - view.x as i32;
+    let y = tile.y - view.y as i32;
+    if x < 0 || y < 0 || x >= view.width as i32 || y >= view.height as i32 {
+        return None;
+    }
+    let margin = (inner.width - view.width) / 2;
+    Some((inner.x + margin + x as u16, inner.y + y as u16))
+}
+
+/// Highlights the tile under the cursor in the map drawn inside `inner`.
+fn draw_cursor(frame: &mut Frame, inner: Rect, view: Rect, cursor: Point) {
+    if let Some((x, y)) = screen_position(inner, view, cursor) {
+        let cell = frame.buffer_mut().get_mut(x, y);
+        cell.set_style(Style::default().add_modifier(Modifier::REVERSED));
+    }
+}
+
+/// Height of the minimap panel, borders included.
+const MINIMAP_HEIGHT: u16 = 10;
+/// Fewest lines left to the message feed below the minimap.
+const MIN_FEED_HEIGHT: u16 = 5;
+
+/// The whole map shrunk into braille dots, with the trains and a rectangle around the tiles
+/// in `view`.
+pub fn render_minimap(ecs: &World, view: Rect) -> Canvas<'static, impl Fn(&mut Context)> {
+    let map = ecs.fetch::<Map>();
+    let theme = ecs.fetch::<Theme>();
+    let glyphs = ecs.fetch::<GlyphSet>();
+    let (width, height) = (map.w() as f64, map.h() as f64);
+    // Canvas y grows upwards, map y downwards
+    let dot = move |p: Point| (p.x as f64 + 0.5, height - p.y as f64 - 0.5);
+
+    let track_color = theme.track;
+    // Lines to the neighbours on the right and below, so the track stays whole when stretched
+    let track: Vec<CanvasLine> = (0..map.h())
+        .flat_map(|y| (0..map.w()).map(move |x| Point::new(x, y)))
+        .flat_map(|p| {
+            let connections = map.get_tile_at_xy(p).shape().connections();
+            let (x1, y1) = dot(p);
+            [Direction::Right, Direction::Down]
+                .into_iter()
+                .filter(move |d| connections.contains(d))
+                .map(move |d| {
+                    let (x2, y2) = dot(p + Point::from(d));
+                    CanvasLine::new(x1, y1, x2, y2, track_color)
+                })
+        })
+        .collect();
+    let trains: Vec<(Vec<(f64, f64)>, _)> = (
+        &ecs.read_storage::<TrainParts>(),
+        &ecs.read_storage::<TrainColors>(),
+    )
+        .join()
+        .map(|(parts, colors)| {
+            let dots = parts.parts.iter().map(|part| dot(part.position)).collect();
+            (dots, theme.trains.unwrap_or(colors.head_color))
+        })
+        .collect();
+    let border_color = theme.border;
+    let viewport = Rectangle {
+        x: view.x as f64,
+        y: height - (view.y + view.height) as f64,
+        width: view.width as f64,
+        height: view.height as f64,
+        color: theme.border,
+    };
+
+    Canvas::default()
+        .block(
+            Block::bordered()
+                .title("Map")
+                .border_set(glyphs.border())
+                .style(Style::default().fg(border_color).bg(theme.background)),
+        )
+        .background_color(theme.background)
+        .marker(Marker::Braille)
+        .x_bounds([0.0, width])
+        .y_bounds([0.0, height])
+        .paint(move |ctx| {
+            for line in &track {
+                ctx.draw(line);
+            }
+            ctx.layer();
+            for (coords, color) in &trains {
+                ctx.draw(&Points {
+                    coords,
+                    color: *color,
+                });
+            }
+            ctx.layer();
+            ctx.draw(&viewport);
+        })
+}
+
+/// Tile under a cell of the minimap drawn inside `inner`.
+pub fn minimap_tile(map: &Map, inner: Rect, column: u16, row: u16) -> Option<Point> {
+    if !inner.contains((column, row).into()) {
+        return None;
+    }
+    let x = (column - inner.x) as i32 * map.w() / inner.width as i32;
+    let y = (row - inner.y) as i32 * map.h() / inner.height as i32;
+    Some(Point::new(x, y))
+}
+
+/// Asks for a bigger terminal instead of drawing a map that does not fit.
+fn render_too_small(frame: &mut Frame, theme: &Theme) {
+    let message = Paragraph::new(format!(
+        "Terminal too small, make it at least {}x{}",
+        MIN_WIDTH, MIN_HEIGHT
+    ))
+    .wrap(Wrap { trim: true })
+    .centered()
+    .style(Style::default().fg(theme.text).bg(theme.background));
+    frame.render_widget(message, frame.size());
+}
+
+/// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
     // This is where you add new widgets.
     // See the following resources:
     // - https://docs.rs/ratatui/latest/ratatui/widgets/index.html
     // - https://github.com/ratatui-org/ratatui/tree/master/examples
+    let theme = Theme::clone(&app.ecs.fetch::<Theme>());
+    let glyphs = *app.ecs.fetch::<GlyphSet>();
+    let size = frame.size();
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        render_too_small(frame, &theme);
+        return;
+    }
+
+    // The feed gives way once the map no longer fits beside it
+    let map_width = app.ecs.fetch::<Map>().w() as u16;
+    let show_feed = size.width >= FEED_WIDTH + MIN_MAP_PANEL_WIDTH.min(map_width + 2);
+    let [map_area, feed_area] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(if show_feed { FEED_WIDTH } else { 0 }),
+    ])
+    .areas(size);
+
+    let title = match (&app.playback, &app.remote) {
+        (Some(playback), _) => format!(
+            "Replay {}/{} x{}{}",
+            app.ecs.fetch::<SimClock>().tick,
+            playback.recording.last_tick(),
+            playback.speed,
+            if playback.paused { " paused" } else { "" }
+        ),
+        (None, Some(remote)) => {
+            let mut title = format!(
+                "Player {} ${}",
+                remote.player + 1,
+                remote.balance().unwrap_or(0)
+            );
+            if !remote.connected {
+                title.push_str(" disconnected");
+            } else if let Some(reason) = &remote.rejection {
+                title = format!("{} - {}", title, reason);
+            }
+            title
+        }
+        (None, None) => String::from("Template"),
+    };
+    let inner = map_area.inner(Margin::new(1, 1));
+    let view = viewport(&app.ecs.fetch::<Map>(), app.cursor, inner);
+    let block = Block::bordered()
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .border_set(glyphs.border())
+        .style(Style::default().fg(theme.border).bg(theme.background));
+    frame.render_widget(block, map_area);
+    let margin = (inner.width - view.width) / 2;
     frame.render_widget(
-        render_map(&app.ecs)
-            .block(
-                Block::bordered()
-                    .title("Template")
-                    .title_alignment(Alignment::Center)
-                    .border_type(BorderType::Rounded),
-            )
-            .style(Style::default().fg(Color::Cyan).bg(Color::Black))
-            .centered(),
-        frame.size(),
+        render_map(&app.ecs, app.overlay).scroll((view.y, view.x)),
+        Rect::new(inner.x + margin, inner.y, view.width, view.height),
+    );
+    if let Some(cursor) = app.cursor {
+        draw_cursor(frame, inner, view, cursor);
+    }
+    // The minimap sits above the feed once the map no longer fits in view
+    let whole_map_shown = {
+        let map = app.ecs.fetch::<Map>();
+        view.width as i32 == map.w() && view.height as i32 == map.h()
+    };
+    let show_minimap = show_feed
+        && !whole_map_shown
+        && glyphs == GlyphSet::Unicode
+        && feed_area.height >= MINIMAP_HEIGHT + MIN_FEED_HEIGHT;
+    let [minimap_area, feed_area] = Layout::vertical([
+        Constraint::Length(if show_minimap { MINIMAP_HEIGHT } else { 0 }),
+        Constraint::Fill(1),
+    ])
+    .areas(feed_area);
+    app.minimap_area = None;
+    if show_minimap {
+        frame.render_widget(render_minimap(&app.ecs, view), minimap_area);
+        app.minimap_area = Some(minimap_area.inner(Margin::new(1, 1)));
+    }
+    if show_feed {
+        frame.render_widget(
+            render_feed(&app.ecs, &app.feed_filter, app.feed_scroll),
+            feed_area,
+        );
+    }
+    if app.show_help {
+        let area = centered(frame.size(), HELP_WIDTH, KeyAction::ALL.len() as u16 + 2);
+        frame.render_widget(Clear, area);
+        frame.render_widget(render_help(&app.key_bindings, &theme, glyphs), area);
+    }
+}
+
+/// Width of the help overlay, borders included.
+const HELP_WIDTH: u16 = 50;
+
+/// Lists every action with the keys bound to it.
+pub fn render_help(bindings: &KeyBindings, theme: &Theme, glyphs: GlyphSet) -> Paragraph<'static> {
+    let lines: Vec<Line> = KeyAction::ALL
+        .iter()
+        .map(|&action| {
+            let keys: Vec<String> = bindings
+                .keys(action)
+                .iter()
+                .map(|k| k.to_string())
+                .collect();
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<20}", keys.join(" ")),
+                    Style::default().fg(theme.border),
+                ),
+                Span::raw(action.description()),
+            ])
+        })
+        .collect();
+
+    Paragraph::new(lines)
+        .block(
+            Block::bordered()
+                .title("Keys")
+                .title_alignment(Alignment::Center)
+                .border_set(glyphs.border()),
+        )
+        .style(Style::default().fg(theme.text).bg(theme.background))
+}
+
+/// A `width` by `height` rectangle in the middle of `area`, cut to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
     )
 }
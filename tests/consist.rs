@@ -0,0 +1,64 @@
+//! Puts trains together from locomotives and cars.
+
+use terminal_transport_game::components::{Point, Traction, TrainParts, Vehicle};
+
+/// A diesel and an electric locomotive pulling a passenger car and a freight wagon.
+fn double_headed() -> TrainParts {
+    TrainParts::new(
+        &[
+            Point::new(4, 0),
+            Point::new(3, 0),
+            Point::new(2, 0),
+            Point::new(1, 0),
+        ],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::locomotive(Traction::Electric, 2.5, 4.0),
+            Vehicle::passenger_car(1.5),
+            Vehicle::freight_wagon(3.0),
+        ],
+    )
+}
+
+#[test]
+fn vehicles_are_placed_from_the_head_back() {
+    let parts = double_headed();
+
+    let positions: Vec<Point> = parts.positions().collect();
+    assert_eq!(
+        positions,
+        vec![
+            Point::new(4, 0),
+            Point::new(3, 0),
+            Point::new(2, 0),
+            Point::new(1, 0),
+        ]
+    );
+    assert!(parts.parts[0].vehicle.is_locomotive());
+    assert!(parts.parts[1].vehicle.is_locomotive());
+    assert_eq!(parts.parts[3].vehicle, Vehicle::freight_wagon(3.0));
+}
+
+#[test]
+#[should_panic(expected = "exactly one position")]
+fn every_vehicle_needs_a_position() {
+    TrainParts::new(
+        &[Point::new(1, 0)],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ],
+    );
+}
+
+#[test]
+fn mass_and_force_add_up_over_the_consist() {
+    let parts = double_headed();
+    assert_eq!(parts.total_mass(), 9.0);
+    // Only the locomotives pull
+    assert_eq!(parts.tractive_force(), 7.0);
+
+    let unpowered = TrainParts::new(&[Point::new(1, 0)], &[Vehicle::passenger_car(1.5)]);
+    assert_eq!(unpowered.total_mass(), 1.5);
+    assert_eq!(unpowered.tractive_force(), 0.0);
+}
@@ -0,0 +1,120 @@
+//! Drives a small world through the JSON control protocol, as a test script would.
+
+use serde_json::Value;
+use specs::{Builder, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{Direction, Point, TrafficLight};
+use terminal_transport_game::control;
+use terminal_transport_game::map::Map;
+
+/// An empty 10x6 ring with a signal facing right on the top side.
+fn ring() -> App {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(10, 6));
+    app.ecs
+        .create_entity()
+        .with(TrafficLight { is_green: true })
+        .with(Point::new(5, 0))
+        .with(Direction::Right)
+        .build();
+    app
+}
+
+/// Sends the requests one per line and parses the response lines.
+fn session(app: &mut App, requests: &[&str]) -> Vec<Value> {
+    let mut output = Vec::new();
+    control::serve(app, requests.join("\n").as_bytes(), &mut output).unwrap();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn spawned_train_runs_until_the_held_signal() {
+    let mut app = ring();
+    let responses = session(
+        &mut app,
+        &[
+            r#"{"command": "spawn_train", "head": {"x": 2, "y": 0}, "direction": "Right", "cars": ["diesel", "passenger"]}"#,
+            r#"{"command": "set_signal", "position": {"x": 5, "y": 0}, "direction": "Right", "green": false}"#,
+            r#"{"command": "step", "ticks": 40}"#,
+            r#"{"command": "query"}"#,
+        ],
+    );
+
+    assert!(responses.iter().all(|r| r["ok"] == true), "{:?}", responses);
+    assert_eq!(responses[2]["result"]["tick"], 40);
+    let state = &responses[3]["result"];
+    assert_eq!(state["signals"][0]["green"], false);
+    assert_eq!(state["signals"][0]["manual"], true);
+    assert_eq!(state["trains"][0]["velocity"], 0.0);
+    assert_eq!(
+        state["trains"][0]["cars"][0],
+        serde_json::json!({"x": 5, "y": 0})
+    );
+}
+
+#[test]
+fn bad_requests_get_an_error_and_quit_ends_the_session() {
+    let mut app = ring();
+    let responses = session(
+        &mut app,
+        &[
+            "not json",
+            r#"{"command": "set_switch", "position": {"x": 3, "y": 3}, "switched": true}"#,
+            r#"{"command": "spawn_train", "head": {"x": 4, "y": 3}, "direction": "Up", "cars": ["diesel"]}"#,
+            r#"{"command": "quit"}"#,
+            r#"{"command": "query"}"#,
+        ],
+    );
+
+    assert_eq!(responses.len(), 4);
+    assert!(responses[..3].iter().all(|r| r["ok"] == false));
+    assert!(responses[0]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid request"));
+    assert_eq!(responses[3]["ok"], true);
+    assert!(!app.running);
+}
+
+#[cfg(unix)]
+#[test]
+fn socket_keeps_serving_after_a_client_fails() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    let path = std::env::temp_dir().join(format!("ttg-control-{}.sock", std::process::id()));
+    let connect = {
+        let path = path.clone();
+        move || loop {
+            match UnixStream::connect(&path) {
+                Ok(stream) => return stream,
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    };
+    let clients = std::thread::spawn(move || {
+        // Not UTF-8, so reading the request fails
+        let mut broken = connect();
+        broken.write_all(b"\xff\xfe\n").unwrap();
+        drop(broken);
+
+        let mut client = connect();
+        writeln!(client, r#"{{"command": "quit"}}"#).unwrap();
+        let mut response = String::new();
+        BufReader::new(client).read_line(&mut response).unwrap();
+        response
+    });
+
+    let mut app = ring();
+    control::serve_socket(&mut app, &path).unwrap();
+    let response: Value = serde_json::from_str(&clients.join().unwrap()).unwrap();
+    assert_eq!(response["ok"], true);
+    assert!(!app.running);
+    assert!(!path.exists());
+}
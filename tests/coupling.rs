@@ -0,0 +1,140 @@
+//! Couples trains running into each other and splits them up again.
+
+use ratatui::style::Color;
+use specs::{Entity, Join, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainColors, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::occupancy::Occupancy;
+use terminal_transport_game::replay::Command;
+use terminal_transport_game::systems::{APPLYING_TRACTION, DRIVING_TRAIN};
+use terminal_transport_game::train::spawn_train;
+
+/// A 12x6 ring where trains only roll, nobody drives them.
+fn ring() -> App {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(12, 6));
+    app.set_system_enabled(DRIVING_TRAIN, false);
+    app.set_system_enabled(APPLYING_TRACTION, false);
+    app
+}
+
+/// A train on the top row with its cars on `xs`, the first one being the head.
+fn train(app: &mut App, xs: &[i32], direction: Direction, velocity: f32) -> Entity {
+    let positions: Vec<Point> = xs.iter().map(|&x| Point::new(x, 0)).collect();
+    let vehicles: Vec<Vehicle> = std::iter::once(Vehicle::locomotive(Traction::Diesel, 2.0, 3.0))
+        .chain(std::iter::repeat(Vehicle::passenger_car(1.5)))
+        .take(xs.len())
+        .collect();
+    let train = spawn_train(
+        &mut app.ecs,
+        TrainParts::new(&positions, &vehicles),
+        direction,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    let mut parameters = app.ecs.write_storage::<TrainParameters>();
+    let params = parameters.get_mut(train).unwrap();
+    params.velocity = velocity;
+    params.throttle = 0.0;
+    drop(parameters);
+    train
+}
+
+/// Positions of the cars of every train.
+fn trains(app: &App) -> Vec<Vec<i32>> {
+    app.ecs
+        .read_storage::<TrainParts>()
+        .join()
+        .map(|t| t.positions().map(|p| p.x).collect())
+        .collect()
+}
+
+fn tick_until_coupled(app: &mut App) {
+    for _ in 0..20 {
+        app.tick();
+        if trains(app).len() == 1 {
+            return;
+        }
+    }
+    panic!("The trains never coupled: {:?}", trains(app));
+}
+
+#[test]
+fn train_couples_onto_the_tail_of_another() {
+    let mut app = ring();
+    train(&mut app, &[8, 7], Direction::Right, 0.0);
+    let pushing = train(&mut app, &[5, 4], Direction::Right, 0.8);
+
+    tick_until_coupled(&mut app);
+    let coupled = trains(&app).remove(0);
+    assert_eq!(coupled.len(), 4);
+    assert_eq!(&coupled[..2], &[8, 7]);
+    let occupancy = app.ecs.fetch::<Occupancy>();
+    assert!(coupled
+        .iter()
+        .all(|&x| occupancy.train_at(Point::new(x, 0)) == Some(pushing)));
+}
+
+#[test]
+fn train_couples_onto_the_head_of_another() {
+    let mut app = ring();
+    train(&mut app, &[7, 8], Direction::Left, 0.0);
+    train(&mut app, &[5, 4], Direction::Right, 0.8);
+
+    tick_until_coupled(&mut app);
+    // The met train is turned around to lead the way
+    let coupled = trains(&app).remove(0);
+    assert_eq!(coupled.len(), 4);
+    assert_eq!(&coupled[..2], &[8, 7]);
+}
+
+#[test]
+fn uncoupling_splits_off_the_cars_behind() {
+    let mut app = ring();
+    let front = train(&mut app, &[8, 7, 6], Direction::Right, 0.0);
+
+    assert!(Command::UncoupleTrain {
+        position: Point::new(7, 0),
+        at: 1,
+    }
+    .apply(&mut app.ecs));
+
+    let mut split = trains(&app);
+    split.sort();
+    assert_eq!(split, vec![vec![7, 6], vec![8]]);
+    let occupancy = app.ecs.fetch::<Occupancy>();
+    assert_eq!(occupancy.train_at(Point::new(8, 0)), Some(front));
+    let back = occupancy.train_at(Point::new(7, 0)).unwrap();
+    assert_ne!(back, front);
+    assert_eq!(occupancy.train_at(Point::new(6, 0)), Some(back));
+    drop(occupancy);
+
+    // Nothing to split off in front of the head or past the end
+    for at in [0, 2] {
+        assert!(!Command::UncoupleTrain {
+            position: Point::new(7, 0),
+            at,
+        }
+        .apply(&mut app.ecs));
+    }
+}
+
+#[test]
+fn failed_uncoupling_leaves_the_train_whole() {
+    let mut app = ring();
+    let train = train(&mut app, &[8, 7, 6], Direction::Right, 0.0);
+    app.ecs.write_storage::<TrainColors>().remove(train);
+
+    assert!(!Command::UncoupleTrain {
+        position: Point::new(7, 0),
+        at: 1,
+    }
+    .apply(&mut app.ecs));
+    assert_eq!(trains(&app), vec![vec![8, 7, 6]]);
+}
@@ -0,0 +1,56 @@
+//! Runs episodes through the agent facing `Env` API.
+
+use terminal_transport_game::env::{Action, Env};
+
+#[test]
+fn same_seed_and_actions_give_the_same_episode() {
+    let run = || {
+        let mut env = Env::new().size(30, 15).trains(3).max_ticks(300);
+        let first = env.reset(7).unwrap();
+        let hold = first.signals[0];
+        let mut steps = vec![(first, 0.0, false)];
+        for i in 0.. {
+            let actions = [Action::SetSignal {
+                position: hold.position,
+                direction: hold.direction,
+                is_green: Some(i % 50 >= 25),
+            }];
+            let step = env.step(&actions);
+            let done = step.2;
+            steps.push(step);
+            if done {
+                break;
+            }
+        }
+        steps
+    };
+
+    let steps = run();
+    assert!(steps.last().unwrap().0.tick <= 300);
+    assert_eq!(steps, run());
+}
+
+#[test]
+fn held_signal_shows_in_the_observation() {
+    let mut env = Env::new().ticks_per_step(10).max_ticks(20);
+    let observation = env.reset(1).unwrap();
+    let signal = &observation.signals[0];
+
+    let (observation, _, done) = env.step(&[Action::SetSignal {
+        position: signal.position,
+        direction: signal.direction,
+        is_green: Some(false),
+    }]);
+    assert!(!observation.signals[0].green);
+    assert!(observation.signals[0].manual);
+    assert_eq!(observation.tick, 10);
+    assert_eq!(
+        observation.occupancy.len(),
+        (observation.width * observation.height) as usize
+    );
+    assert!(!done || observation.trains.iter().all(|t| t.arrived));
+
+    let (observation, _, done) = env.step(&[]);
+    assert_eq!(observation.tick, 20);
+    assert!(done);
+}
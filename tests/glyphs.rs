@@ -0,0 +1,37 @@
+//! Picks the glyph set from the environment.
+
+use terminal_transport_game::glyphs::GlyphSet;
+
+fn detect(vars: &[(&str, &str)]) -> GlyphSet {
+    GlyphSet::from_env(|name| {
+        vars.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.to_string())
+    })
+}
+
+#[test]
+fn locale_decides_the_glyphs() {
+    assert_eq!(detect(&[("LANG", "en_GB.UTF-8")]), GlyphSet::Unicode);
+    assert_eq!(detect(&[("LANG", "C")]), GlyphSet::Ascii);
+    // LC_ALL wins over LANG, and an empty variable counts as unset
+    assert_eq!(
+        detect(&[("LC_ALL", "POSIX"), ("LANG", "en_US.utf8")]),
+        GlyphSet::Ascii
+    );
+    assert_eq!(
+        detect(&[("LC_ALL", ""), ("LANG", "en_US.utf8")]),
+        GlyphSet::Unicode
+    );
+    assert_eq!(
+        detect(&[("TERM", "vt100"), ("LANG", "en_US.UTF-8")]),
+        GlyphSet::Ascii
+    );
+}
+
+#[test]
+fn glyph_set_is_named_on_the_command_line() {
+    assert_eq!("ascii".parse(), Ok(GlyphSet::Ascii));
+    assert_eq!("unicode".parse(), Ok(GlyphSet::Unicode));
+    assert!("ebcdic".parse::<GlyphSet>().is_err());
+}
@@ -0,0 +1,63 @@
+//! Loads key bindings and drives the app with them.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::Point;
+use terminal_transport_game::handler::handle_key_events;
+use terminal_transport_game::keymap::{Key, KeyAction, KeyBindings};
+use terminal_transport_game::map::Map;
+
+fn press(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+    handle_key_events(KeyEvent::new(code, modifiers), app).unwrap();
+}
+
+#[test]
+fn keys_read_back_as_written() {
+    for key in ["q", "Ctrl-c", "Alt-Ctrl-x", "Space", "PageUp", "F5", "?"] {
+        let parsed: Key = key.parse().unwrap();
+        let again: Key = parsed.to_string().parse().unwrap();
+        assert_eq!(parsed, again, "{}", key);
+    }
+    assert!("Hyper-q".parse::<Key>().is_err());
+}
+
+#[test]
+fn file_overrides_only_the_actions_it_names() {
+    let bindings =
+        KeyBindings::from_json(r#"{"cursor_left": ["Left", "a"], "quit": ["Ctrl-q"]}"#).unwrap();
+    let defaults = KeyBindings::default();
+
+    assert_eq!(
+        bindings.keys(KeyAction::CursorLeft),
+        ["Left".parse().unwrap(), "a".parse().unwrap()]
+    );
+    assert_eq!(bindings.keys(KeyAction::Quit), ["Ctrl-q".parse().unwrap()]);
+    assert_eq!(
+        bindings.keys(KeyAction::CursorRight),
+        defaults.keys(KeyAction::CursorRight)
+    );
+
+    assert!(KeyBindings::from_json(r#"{"fly": ["f"]}"#).is_err());
+    assert!(KeyBindings::from_json(r#"{"quit": ["Hyper-q"]}"#).is_err());
+}
+
+#[test]
+fn rebound_keys_drive_the_app() {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(10, 6));
+    app.key_bindings =
+        KeyBindings::from_json(r#"{"cursor_right": ["Right"], "quit": ["Ctrl-q"]}"#).unwrap();
+
+    // Right is the counter's by default and moves the cursor once bound to it
+    press(&mut app, KeyCode::Right, KeyModifiers::NONE);
+    press(&mut app, KeyCode::Right, KeyModifiers::NONE);
+    assert_eq!(app.cursor, Some(Point::new(1, 0)));
+
+    press(&mut app, KeyCode::Char('?'), KeyModifiers::SHIFT);
+    assert!(app.show_help);
+
+    press(&mut app, KeyCode::Char('q'), KeyModifiers::NONE);
+    assert!(app.running);
+    press(&mut app, KeyCode::Char('q'), KeyModifiers::CONTROL);
+    assert!(!app.running);
+}
@@ -0,0 +1,145 @@
+//! Checks the rules of the track on a map.
+
+use terminal_transport_game::components::{Direction, Point};
+use terminal_transport_game::map::{Map, MapIssue, RailShape};
+
+/// A junction at (1, 1) joining track from the left, right and below.
+fn junction() -> Map {
+    let mut map = Map::empty(3, 3);
+    map.put_tile_at_xy(Point::new(0, 1), RailShape::EndRight);
+    map.put_tile_at_xy(Point::new(1, 1), RailShape::TBottom);
+    map.put_tile_at_xy(Point::new(2, 1), RailShape::EndLeft);
+    map.put_tile_at_xy(Point::new(1, 2), RailShape::EndTop);
+    map
+}
+
+#[test]
+fn switch_picks_the_way_out_of_a_junction() {
+    let mut map = junction();
+    let at = Point::new(1, 1);
+
+    // Straight on by default, the branch once switched
+    assert_eq!(
+        map.try_next_direction(at, Direction::Right),
+        Some(Direction::Right)
+    );
+    map.toggle_switch(at);
+    assert_eq!(
+        map.try_next_direction(at, Direction::Right),
+        Some(Direction::Down)
+    );
+    map.set_switch(at, false);
+    assert_eq!(
+        map.try_next_direction(at, Direction::Right),
+        Some(Direction::Right)
+    );
+
+    // Coming up the branch the junction turns left until switched, and there is no way back down
+    assert_eq!(
+        map.try_next_direction(at, Direction::Up),
+        Some(Direction::Left)
+    );
+    assert_eq!(map.try_next_direction(at, Direction::Down), None);
+}
+
+#[test]
+fn trains_enter_only_through_the_rails_of_a_tile() {
+    let map = junction();
+    let at = Point::new(1, 1);
+    assert!(map.can_enter(at, Direction::Right));
+    assert!(map.can_enter(at, Direction::Left));
+    assert!(map.can_enter(at, Direction::Up));
+    assert!(!map.can_enter(at, Direction::Down));
+    assert!(!map.can_enter(Point::new(3, 1), Direction::Right));
+}
+
+#[test]
+fn simple_ring_is_valid() {
+    let map = Map::simple_ring(12, 6);
+    assert_eq!(map.validate(&[]), Vec::new());
+    assert_eq!(
+        map.validate(&[(Point::new(5, 0), Direction::Left)]),
+        Vec::new()
+    );
+}
+
+#[test]
+fn rails_leading_into_nothing_dangle() {
+    let mut map = Map::simple_ring(12, 6);
+    map.put_tile_at_xy(Point::new(5, 0), RailShape::TBottom);
+    assert_eq!(
+        map.validate(&[]),
+        vec![MapIssue::DanglingConnection {
+            position: Point::new(5, 0),
+            side: Direction::Down,
+        }]
+    );
+
+    // Off the edge of the map as well
+    let mut map = Map::empty(1, 1);
+    map.put_tile_at_xy(Point::new(0, 0), RailShape::EndLeft);
+    assert_eq!(
+        map.validate(&[]),
+        vec![MapIssue::DanglingConnection {
+            position: Point::new(0, 0),
+            side: Direction::Left,
+        }]
+    );
+}
+
+#[test]
+fn rails_onto_a_tile_that_does_not_connect_back_are_mismatched() {
+    let mut map = Map::simple_ring(12, 6);
+    map.put_tile_at_xy(Point::new(5, 0), RailShape::TBottom);
+    map.put_tile_at_xy(Point::new(5, 1), RailShape::TurnTopRight);
+    // The siding turns back up into the ring, which has no rails leading down there
+    map.put_tile_at_xy(Point::new(6, 1), RailShape::TurnTopLeft);
+    assert_eq!(
+        map.validate(&[]),
+        vec![MapIssue::MismatchedNeighbour {
+            position: Point::new(6, 1),
+            side: Direction::Up,
+            neighbour: RailShape::Horizontal,
+        }]
+    );
+}
+
+#[test]
+fn track_cut_off_from_the_rest_is_unreachable() {
+    let mut map = Map::simple_ring(12, 6);
+    map.put_tile_at_xy(Point::new(3, 2), RailShape::EndRight);
+    map.put_tile_at_xy(Point::new(4, 2), RailShape::EndLeft);
+    assert_eq!(
+        map.validate(&[]),
+        vec![MapIssue::UnreachableSection {
+            start: Point::new(3, 2),
+            size: 2,
+        }]
+    );
+}
+
+#[test]
+fn signals_must_stand_on_track_and_face_along_it() {
+    let map = Map::simple_ring(12, 6);
+    assert_eq!(
+        map.validate(&[
+            (Point::new(5, 2), Direction::Right),
+            (Point::new(20, 0), Direction::Right),
+        ]),
+        vec![
+            MapIssue::SignalOffTrack {
+                position: Point::new(5, 2),
+            },
+            MapIssue::SignalOffTrack {
+                position: Point::new(20, 0),
+            },
+        ]
+    );
+    assert_eq!(
+        map.validate(&[(Point::new(5, 0), Direction::Down)]),
+        vec![MapIssue::SignalFacingOffTrack {
+            position: Point::new(5, 0),
+            direction: Direction::Down,
+        }]
+    );
+}
@@ -0,0 +1,47 @@
+//! Generates layouts from seeds.
+
+use terminal_transport_game::components::Point;
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::map_generator::MapGenerator;
+
+fn shapes(map: &Map) -> Vec<RailShape> {
+    (0..map.h())
+        .flat_map(|y| (0..map.w()).map(move |x| Point::new(x, y)))
+        .map(|p| map.get_tile_at_xy(p).shape())
+        .collect()
+}
+
+#[test]
+fn same_seed_gives_the_same_map() {
+    let generator = MapGenerator::new(42).size(50, 25).density(0.7);
+    let first = generator.generate().unwrap();
+    let second = generator.generate().unwrap();
+
+    assert_eq!(shapes(&first.map), shapes(&second.map));
+    assert_eq!(first.signals, second.signals);
+    assert_eq!(first.stations, second.stations);
+
+    let other = MapGenerator::new(43).size(50, 25).density(0.7);
+    assert_ne!(shapes(&first.map), shapes(&other.generate().unwrap().map));
+}
+
+#[test]
+fn generated_maps_are_valid() {
+    for seed in 0..20 {
+        for (width, height, density) in [(12, 8, 0.0), (40, 20, 0.5), (80, 40, 1.0)] {
+            let generated = MapGenerator::new(seed)
+                .size(width, height)
+                .density(density)
+                .generate()
+                .unwrap();
+            assert_eq!(
+                generated.map.validate(&generated.signals),
+                Vec::new(),
+                "seed {} at {}x{}",
+                seed,
+                width,
+                height
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Collects statistics on trains and blocks and writes them out.
+
+use std::fs;
+
+use ratatui::style::Color;
+use specs::{Builder, Entity, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrafficLight, TrainColors, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::metrics::{BlockSample, Metrics, TrainSample};
+use terminal_transport_game::systems::{ACTIVE_TRAFFIC_LIGHT, APPLYING_TRACTION, DRIVING_TRAIN};
+use terminal_transport_game::train::spawn_train;
+
+/// A ring sampled every 5 ticks, where nothing drives the trains or changes the signals.
+fn ring() -> App {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(12, 6));
+    app.set_system_enabled(DRIVING_TRAIN, false);
+    app.set_system_enabled(APPLYING_TRACTION, false);
+    app.set_system_enabled(ACTIVE_TRAFFIC_LIGHT, false);
+    app.enable_metrics(5);
+    app
+}
+
+/// A standing two-car train on the top row heading right, its head at (5, 0).
+fn standing_train(app: &mut App) -> Entity {
+    let train = spawn_train(
+        &mut app.ecs,
+        TrainParts::new(
+            &[Point::new(5, 0), Point::new(4, 0)],
+            &[
+                Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+                Vehicle::passenger_car(1.5),
+            ],
+        ),
+        Direction::Right,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    app.ecs
+        .write_storage::<TrainParameters>()
+        .get_mut(train)
+        .unwrap()
+        .throttle = 0.0;
+    train
+}
+
+#[test]
+fn standing_at_a_red_signal_counts_as_waiting() {
+    let mut app = ring();
+    let train = standing_train(&mut app);
+    app.ecs
+        .create_entity()
+        .with(TrafficLight { is_green: false })
+        .with(Point::new(5, 0))
+        .with(Direction::Right)
+        .build();
+
+    for _ in 0..10 {
+        app.tick();
+    }
+
+    let metrics = app.ecs.fetch::<Metrics>();
+    let totals = metrics.train_totals[&train];
+    assert_eq!(totals.stopped, 10);
+    assert_eq!(totals.signal_wait, 10);
+    let ticks: Vec<u64> = metrics.trains.iter().map(|s| s.tick).collect();
+    assert_eq!(ticks, vec![0, 5]);
+    let last = metrics.trains.last().unwrap();
+    assert_eq!((last.stopped_ticks, last.signal_wait_ticks), (6, 6));
+    assert_eq!(metrics.blocks.len(), 2);
+    assert!(metrics.blocks.iter().all(|b| b.occupied));
+    assert_eq!(metrics.blocks.last().unwrap().occupancy, 1.0);
+}
+
+#[test]
+fn signal_held_red_over_an_empty_block_is_not_occupied() {
+    let mut app = ring();
+    app.ecs
+        .create_entity()
+        .with(TrafficLight { is_green: false })
+        .with(Point::new(5, 0))
+        .with(Direction::Right)
+        .build();
+
+    for _ in 0..6 {
+        app.tick();
+    }
+
+    let metrics = app.ecs.fetch::<Metrics>();
+    assert_eq!(metrics.blocks.len(), 2);
+    assert!(metrics.blocks.iter().all(|b| !b.occupied));
+    assert_eq!(metrics.blocks.last().unwrap().occupancy, 0.0);
+}
+
+#[test]
+fn train_reusing_an_id_starts_from_zero() {
+    let mut app = ring();
+    let first = standing_train(&mut app);
+    for _ in 0..5 {
+        app.tick();
+    }
+    app.ecs.delete_entity(first).unwrap();
+    app.ecs.maintain();
+
+    let second = standing_train(&mut app);
+    assert_eq!(second.id(), first.id());
+    app.tick();
+
+    let metrics = app.ecs.fetch::<Metrics>();
+    assert!(!metrics.train_totals.contains_key(&first));
+    assert_eq!(metrics.train_totals[&second].stopped, 1);
+    let sample = metrics.trains.last().unwrap();
+    assert_eq!(sample.tick, 5);
+    assert_eq!(sample.stopped_ticks, 1);
+    assert_ne!(sample.generation, metrics.trains[0].generation);
+}
+
+fn samples() -> Metrics {
+    let mut metrics = Metrics::new(5);
+    metrics.trains.push(TrainSample {
+        tick: 5,
+        train: 3,
+        generation: 1,
+        speed: 0.5,
+        distance: 12,
+        stopped_ticks: 2,
+        signal_wait_ticks: 1,
+    });
+    metrics.blocks.push(BlockSample {
+        tick: 5,
+        signal_x: 4,
+        signal_y: 0,
+        direction: Direction::Right,
+        occupied: true,
+        occupancy: 0.5,
+    });
+    metrics
+}
+
+#[test]
+fn csv_export_writes_a_file_per_table() {
+    let dir = std::env::temp_dir().join(format!("ttg-metrics-csv-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    samples().export(&dir.join("run.csv")).unwrap();
+    let trains = fs::read_to_string(dir.join("run_trains.csv")).unwrap();
+    let blocks = fs::read_to_string(dir.join("run_blocks.csv")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        trains,
+        "tick,train,generation,speed,distance,stopped_ticks,signal_wait_ticks\n5,3,1,0.5,12,2,1\n"
+    );
+    assert_eq!(
+        blocks,
+        "tick,signal_x,signal_y,direction,occupied,occupancy\n5,4,0,Right,true,0.5\n"
+    );
+}
+
+#[test]
+fn json_export_holds_both_tables() {
+    let dir = std::env::temp_dir().join(format!("ttg-metrics-json-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("run.json");
+    samples().export(&path).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(json["trains"][0]["train"], 3);
+    assert_eq!(json["trains"][0]["generation"], 1);
+    assert_eq!(json["trains"][0]["distance"], 12);
+    assert_eq!(json["blocks"][0]["direction"], "Right");
+    assert_eq!(json["blocks"][0]["occupancy"], 0.5);
+}
@@ -0,0 +1,299 @@
+//! Plays a two player game against a server on localhost.
+
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use specs::{Join, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Owner, Point, Traction, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::handler::handle_key_events;
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::multiplayer::{
+    play, RemoteGame, Server, STARTING_MONEY, TRACK_COST, TRAIN_COST,
+};
+use terminal_transport_game::replay::Command;
+use terminal_transport_game::systems::{APPLYING_TRACTION, DRIVING_TRAIN, MOVING_TRAIN};
+
+/// A server for an empty 12x6 ring.
+fn server() -> (App, Server) {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(12, 6));
+    let server = Server::bind(&mut app, "127.0.0.1:0").unwrap();
+    (app, server)
+}
+
+/// Connects a player, running the server until it has taken them in.
+fn join(app: &mut App, server: &mut Server) -> App {
+    let addr = server.local_addr().unwrap();
+    let client = thread::spawn(move || RemoteGame::connect(addr).map_err(|e| e.to_string()));
+    let players = server.players();
+    while server.players() == players {
+        server.step(app);
+    }
+    let mut player = App::new();
+    player.join(client.join().unwrap().unwrap());
+    player
+}
+
+/// Runs the server until the player sees what they are waiting for.
+fn wait_for(app: &mut App, server: &mut Server, player: &mut App, seen: impl Fn(&App) -> bool) {
+    for _ in 0..200 {
+        server.step(app);
+        thread::sleep(Duration::from_millis(5));
+        player.tick();
+        if seen(player) {
+            return;
+        }
+    }
+    panic!("The player never saw it");
+}
+
+fn rejection(player: &App) -> Option<&str> {
+    player.remote.as_ref().unwrap().rejection.as_deref()
+}
+
+fn train_count(app: &App) -> usize {
+    app.ecs.read_storage::<TrainParts>().join().count()
+}
+
+#[test]
+fn players_own_their_trains_and_pay_for_them() {
+    let (mut app, mut server) = server();
+    let mut first = join(&mut app, &mut server);
+    let mut second = join(&mut app, &mut server);
+    assert_eq!(first.remote.as_ref().unwrap().player, 0);
+    assert_eq!(second.remote.as_ref().unwrap().player, 1);
+
+    first.execute(Command::SpawnTrain {
+        head: Point::new(4, 0),
+        direction: Direction::Right,
+        vehicles: vec![
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ],
+    });
+    wait_for(&mut app, &mut server, &mut first, |p| train_count(p) == 1);
+    wait_for(&mut app, &mut server, &mut second, |p| train_count(p) == 1);
+    assert_eq!(
+        second.remote.as_ref().unwrap().balances,
+        vec![STARTING_MONEY - TRAIN_COST, STARTING_MONEY]
+    );
+
+    // Only the owner may turn the train around
+    let head = app
+        .ecs
+        .read_storage::<TrainParts>()
+        .join()
+        .next()
+        .unwrap()
+        .parts[0]
+        .position;
+    second.execute(Command::ReverseTrain(head));
+    wait_for(&mut app, &mut server, &mut second, |p| {
+        rejection(p) == Some("Not your train")
+    });
+}
+
+#[test]
+fn spawn_key_puts_a_train_under_the_cursor_for_the_player() {
+    let (mut app, mut server) = server();
+    app.set_system_enabled(MOVING_TRAIN, false);
+    let _first = join(&mut app, &mut server);
+    let mut second = join(&mut app, &mut server);
+
+    second.cursor = Some(Point::new(4, 5));
+    handle_key_events(KeyEvent::from(KeyCode::Char('s')), &mut second).unwrap();
+    wait_for(&mut app, &mut server, &mut second, |p| train_count(p) == 1);
+
+    let parts = app.ecs.read_storage::<TrainParts>();
+    let owners = app.ecs.read_storage::<Owner>();
+    let (parts, owner) = (&parts, &owners).join().next().unwrap();
+    assert_eq!(parts.parts[0].position, Point::new(4, 5));
+    assert_eq!(parts.parts.len(), 2);
+    assert_eq!(owner.player, 1);
+    assert_eq!(
+        second.remote.as_ref().unwrap().balance(),
+        Some(STARTING_MONEY - TRAIN_COST)
+    );
+}
+
+#[test]
+fn built_track_shows_up_for_both_players() {
+    let (mut app, mut server) = server();
+    let mut first = join(&mut app, &mut server);
+    let mut second = join(&mut app, &mut server);
+
+    second.execute(Command::BuildTrack(Point::new(5, 1)));
+    let built = |p: &App| {
+        p.ecs
+            .fetch::<Map>()
+            .get_tile_at_xy(Point::new(5, 1))
+            .shape()
+            != RailShape::Empty
+    };
+    wait_for(&mut app, &mut server, &mut first, built);
+    wait_for(&mut app, &mut server, &mut second, built);
+
+    for player in [&first, &second] {
+        let map = player.ecs.fetch::<Map>();
+        assert_eq!(
+            map.get_tile_at_xy(Point::new(5, 1)).shape(),
+            RailShape::EndTop
+        );
+        assert_eq!(
+            map.get_tile_at_xy(Point::new(5, 0)).shape(),
+            RailShape::TBottom
+        );
+    }
+    assert_eq!(
+        first.remote.as_ref().unwrap().balances,
+        vec![STARTING_MONEY, STARTING_MONEY - TRACK_COST]
+    );
+
+    // The new tile belongs to the second player
+    first.execute(Command::ToggleSwitch(Point::new(5, 1)));
+    wait_for(&mut app, &mut server, &mut first, |p| {
+        rejection(p) == Some("Not your track")
+    });
+}
+
+#[test]
+fn players_cannot_build_onto_or_spawn_on_the_other_players_track() {
+    let (mut app, mut server) = server();
+    let mut first = join(&mut app, &mut server);
+    let mut second = join(&mut app, &mut server);
+
+    second.execute(Command::BuildTrack(Point::new(5, 1)));
+    wait_for(&mut app, &mut server, &mut first, |p| {
+        p.ecs
+            .fetch::<Map>()
+            .get_tile_at_xy(Point::new(5, 1))
+            .shape()
+            == RailShape::EndTop
+    });
+
+    // Track beside it would turn the second player's tile into a junction
+    first.execute(Command::BuildTrack(Point::new(6, 1)));
+    wait_for(&mut app, &mut server, &mut first, |p| {
+        rejection(p) == Some("Not your track")
+    });
+    assert_eq!(
+        app.ecs
+            .fetch::<Map>()
+            .get_tile_at_xy(Point::new(6, 1))
+            .shape(),
+        RailShape::Empty
+    );
+
+    first.remote.as_mut().unwrap().rejection = None;
+    first.execute(Command::SpawnTrain {
+        head: Point::new(5, 1),
+        direction: Direction::Down,
+        vehicles: vec![Vehicle::locomotive(Traction::Diesel, 2.0, 3.0)],
+    });
+    wait_for(&mut app, &mut server, &mut first, |p| {
+        rejection(p) == Some("Not your track")
+    });
+    assert_eq!(train_count(&app), 0);
+    assert_eq!(
+        first.remote.as_ref().unwrap().balances,
+        vec![STARTING_MONEY, STARTING_MONEY - TRACK_COST]
+    );
+}
+
+#[test]
+fn a_third_player_is_turned_away() {
+    let (mut app, mut server) = server();
+    let _first = join(&mut app, &mut server);
+    let _second = join(&mut app, &mut server);
+
+    let addr = server.local_addr().unwrap();
+    let client = thread::spawn(move || RemoteGame::connect(addr).map(|_| ()).is_err());
+    while !client.is_finished() {
+        server.step(&mut app);
+    }
+    assert!(client.join().unwrap());
+    assert_eq!(server.players(), 2);
+}
+
+#[test]
+fn a_player_who_leaves_frees_their_seat() {
+    let (mut app, mut server) = server();
+    let mut first = join(&mut app, &mut server);
+
+    // Someone connects and goes away without a word
+    let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    while server.players() < 2 {
+        server.step(&mut app);
+    }
+    stream.shutdown(Shutdown::Both).unwrap();
+    drop(stream);
+    for _ in 0..200 {
+        server.step(&mut app);
+        if server.players() == 1 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(server.players(), 1);
+
+    // The game goes on for the one who stayed
+    first.execute(Command::BuildTrack(Point::new(5, 1)));
+    wait_for(&mut app, &mut server, &mut first, |p| {
+        p.remote.as_ref().unwrap().balances[0] == STARTING_MONEY - TRACK_COST
+    });
+}
+
+#[test]
+fn trains_of_different_players_do_not_couple() {
+    let (mut app, _server) = server();
+    app.set_system_enabled(DRIVING_TRAIN, false);
+    app.set_system_enabled(APPLYING_TRACTION, false);
+    let vehicles = vec![
+        Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+        Vehicle::passenger_car(1.5),
+    ];
+    for (player, head) in [(0, Point::new(8, 0)), (1, Point::new(5, 0))] {
+        play(
+            &mut app,
+            player,
+            Command::SpawnTrain {
+                head,
+                direction: Direction::Right,
+                vehicles: vehicles.clone(),
+            },
+        )
+        .unwrap();
+    }
+    {
+        let parts = app.ecs.read_storage::<TrainParts>();
+        let mut parameters = app.ecs.write_storage::<TrainParameters>();
+        for (parts, params) in (&parts, &mut parameters).join() {
+            params.throttle = 0.0;
+            params.velocity = if parts.parts[0].position.x == 5 {
+                0.8
+            } else {
+                0.0
+            };
+        }
+    }
+
+    for _ in 0..20 {
+        app.tick();
+    }
+
+    // The second player's train stops right behind the first one's and both keep their cars
+    let parts = app.ecs.read_storage::<TrainParts>();
+    let owners = app.ecs.read_storage::<Owner>();
+    let mut trains: Vec<(u8, Vec<i32>)> = (&parts, &owners)
+        .join()
+        .map(|(t, o)| (o.player, t.positions().map(|p| p.x).collect()))
+        .collect();
+    trains.sort();
+    assert_eq!(trains, vec![(0, vec![8, 7]), (1, vec![6, 5])]);
+}
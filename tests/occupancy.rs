@@ -0,0 +1,61 @@
+//! Keeps the grid of occupied tiles in step with the map.
+
+use specs::{World, WorldExt};
+
+use terminal_transport_game::components::{Point, Traction, TrainParts, Vehicle};
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::occupancy::Occupancy;
+
+fn parts(xs: &[i32]) -> TrainParts {
+    let positions: Vec<Point> = xs.iter().map(|&x| Point::new(x, 0)).collect();
+    let vehicles: Vec<Vehicle> = std::iter::once(Vehicle::locomotive(Traction::Diesel, 2.0, 3.0))
+        .chain(std::iter::repeat(Vehicle::passenger_car(1.5)))
+        .take(xs.len())
+        .collect();
+    TrainParts::new(&positions, &vehicles)
+}
+
+#[test]
+fn grid_is_refilled_when_the_map_changes_or_is_replaced() {
+    let world = World::new();
+    let (first, second) = (world.entities().create(), world.entities().create());
+    let (first_parts, second_parts) = (parts(&[3, 2]), parts(&[8, 7]));
+
+    let mut map = Map::simple_ring(12, 6);
+    let mut occupancy = Occupancy::default();
+    occupancy.fit(&map, [(first, &first_parts)].into_iter());
+    assert_eq!(occupancy.train_at(Point::new(3, 0)), Some(first));
+
+    // The same map keeps the grid as it is
+    occupancy.fit(&map, [(second, &second_parts)].into_iter());
+    assert_eq!(occupancy.train_at(Point::new(3, 0)), Some(first));
+    assert_eq!(occupancy.train_at(Point::new(8, 0)), None);
+
+    // A map of the same size in its place
+    let replacement = Map::simple_ring(12, 6);
+    occupancy.fit(&replacement, [(second, &second_parts)].into_iter());
+    assert_eq!(occupancy.train_at(Point::new(3, 0)), None);
+    assert_eq!(occupancy.train_at(Point::new(8, 0)), Some(second));
+
+    // Track relaid on the map
+    map.put_tile_at_xy(Point::new(5, 2), RailShape::Cross);
+    occupancy.fit(&map, [(first, &first_parts)].into_iter());
+    assert_eq!(occupancy.train_at(Point::new(3, 0)), Some(first));
+    assert_eq!(occupancy.train_at(Point::new(8, 0)), None);
+}
+
+#[test]
+fn tiles_of_the_other_trains_are_listed() {
+    let world = World::new();
+    let (first, second) = (world.entities().create(), world.entities().create());
+    let (first_parts, second_parts) = (parts(&[3, 2]), parts(&[8, 7]));
+
+    let mut occupancy = Occupancy::default();
+    occupancy.fit(
+        &Map::simple_ring(12, 6),
+        [(first, &first_parts), (second, &second_parts)].into_iter(),
+    );
+    let mut others: Vec<Point> = occupancy.occupied_by_others(first).collect();
+    others.sort_by_key(|p| (p.y, p.x));
+    assert_eq!(others, vec![Point::new(7, 0), Point::new(8, 0)]);
+}
@@ -0,0 +1,276 @@
+//! Renders small worlds onto a `TestBackend` and compares the frames with the
+//! snapshots in `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to accept changes.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::{env, fs};
+
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::Terminal;
+use specs::{Builder, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Station, Traction, TrafficLight, TrainColors, TrainParts, Vehicle,
+};
+use terminal_transport_game::glyphs::GlyphSet;
+use terminal_transport_game::handler::handle_mouse_events;
+use terminal_transport_game::map::Map;
+use terminal_transport_game::train::spawn_train;
+use terminal_transport_game::ui;
+
+/// A 12x6 ring with a three car train, two signals and a station.
+fn small_world() -> App {
+    let mut app = App::new();
+    let mut map = Map::simple_ring(12, 6);
+    for x in 3..=8 {
+        let height = 3 - (x - 5i32).abs().min(3);
+        map.set_elevation(Point::new(x, 0), height as f32 * 0.1);
+    }
+    app.ecs.insert(map);
+
+    let parts = TrainParts::new(
+        &[Point::new(0, 2), Point::new(0, 3), Point::new(0, 4)],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+            Vehicle::freight_wagon(2.0),
+        ],
+    );
+    spawn_train(
+        &mut app.ecs,
+        parts,
+        Direction::Up,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+
+    for (position, direction) in [
+        (Point::new(6, 0), Direction::Right),
+        (Point::new(5, 5), Direction::Left),
+    ] {
+        app.ecs
+            .create_entity()
+            .with(TrafficLight { is_green: true })
+            .with(position)
+            .with(direction)
+            .build();
+    }
+
+    app.ecs
+        .create_entity()
+        .with(Station {
+            name: String::from("Central"),
+        })
+        .with(Point::new(11, 3))
+        .build();
+
+    app
+}
+
+/// Runs `ticks` ticks and draws the whole user interface.
+fn render_after(app: &mut App, ticks: usize) -> Buffer {
+    for _ in 0..ticks {
+        app.tick();
+    }
+    // The map with its border, next to the message feed
+    render_in(app, 14 + 40, 8)
+}
+
+/// Draws the whole user interface in a terminal of the given size.
+fn render_in(app: &mut App, width: u16, height: u16) -> Buffer {
+    let mut terminal =
+        Terminal::new(TestBackend::new(width, height)).expect("The test backend never fails");
+    terminal
+        .draw(|frame| ui::render(app, frame))
+        .expect("The test backend never fails");
+    terminal.backend().buffer().clone()
+}
+
+/// Writes out the symbols of the buffer followed by grids of their colours,
+/// each colour replaced by a letter explained in the legend at the end.
+fn describe(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut out = String::from("symbols:\n");
+    for y in 0..area.height {
+        for x in 0..area.width {
+            out.push_str(buffer.get(x, y).symbol());
+        }
+        out.push('\n');
+    }
+
+    let mut legend: Vec<Color> = Vec::new();
+    let mut letters: HashMap<Color, char> = HashMap::new();
+    let mut letter = |color: Color| {
+        *letters.entry(color).or_insert_with(|| {
+            legend.push(color);
+            (b'a' + legend.len() as u8 - 1) as char
+        })
+    };
+    let mut fg = String::from("foreground:\n");
+    let mut bg = String::from("background:\n");
+    for y in 0..area.height {
+        for x in 0..area.width {
+            let cell = buffer.get(x, y);
+            fg.push(letter(cell.fg));
+            bg.push(letter(cell.bg));
+        }
+        fg.push('\n');
+        bg.push('\n');
+    }
+    out.push_str(&fg);
+    out.push_str(&bg);
+
+    out.push_str("legend:\n");
+    for (i, color) in legend.iter().enumerate() {
+        writeln!(out, "{} = {:?}", (b'a' + i as u8) as char, color).expect("Writing to a string");
+    }
+    out
+}
+
+fn assert_snapshot(name: &str, buffer: &Buffer) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", name]
+        .iter()
+        .collect::<PathBuf>()
+        .with_extension("txt");
+    let actual = describe(buffer);
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &actual).expect("Failed to write the snapshot");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "No snapshot at {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "Frame differs from {}, run with UPDATE_SNAPSHOTS=1 to accept it\n\nexpected:\n{}\nactual:\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn standing_train() {
+    let mut app = small_world();
+    assert_snapshot("standing_train", &render_after(&mut app, 0));
+}
+
+#[test]
+fn train_turning_the_corner() {
+    let mut app = small_world();
+    assert_snapshot("train_turning_the_corner", &render_after(&mut app, 4));
+}
+
+#[test]
+fn signal_turns_red_behind_train() {
+    let mut app = small_world();
+    assert_snapshot("signal_turns_red_behind_train", &render_after(&mut app, 12));
+}
+
+#[test]
+fn gradient_overlay() {
+    let mut app = small_world();
+    app.toggle_gradient_overlay();
+    assert_snapshot("gradient_overlay", &render_after(&mut app, 0));
+}
+
+#[test]
+fn feed_showing_only_signals() {
+    let mut app = small_world();
+    app.cycle_feed_type_filter();
+    assert_snapshot("feed_showing_only_signals", &render_after(&mut app, 40));
+}
+
+#[test]
+fn ascii_glyphs() {
+    let mut app = small_world();
+    app.ecs.insert(GlyphSet::Ascii);
+    assert_snapshot("ascii_glyphs", &render_after(&mut app, 12));
+}
+
+#[test]
+fn narrow_terminal_hides_the_feed() {
+    let mut app = small_world();
+    assert_snapshot(
+        "narrow_terminal_hides_the_feed",
+        &render_in(&mut app, 30, 8),
+    );
+}
+
+#[test]
+fn terminal_too_small() {
+    let mut app = small_world();
+    assert_snapshot("terminal_too_small", &render_in(&mut app, 16, 4));
+}
+
+#[test]
+fn camera_follows_the_cursor() {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(60, 20));
+    app.cursor = Some(Point::new(45, 19));
+    // The cursor in the bottom row sits in the middle of the view, as far right as the map allows
+    let view = ui::viewport(&app.ecs.fetch::<Map>(), app.cursor, Rect::new(1, 1, 28, 6));
+    assert_eq!(view, Rect::new(31, 14, 28, 6));
+    assert_snapshot("camera_follows_the_cursor", &render_in(&mut app, 30, 8));
+}
+
+/// A 60x20 ring with a train in the bottom right corner, too big to be seen whole.
+fn large_world() -> App {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(60, 20));
+    let parts = TrainParts::new(
+        &[Point::new(50, 19), Point::new(51, 19)],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ],
+    );
+    spawn_train(
+        &mut app.ecs,
+        parts,
+        Direction::Left,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    app
+}
+
+#[test]
+fn minimap_of_a_large_map() {
+    let mut app = large_world();
+    app.cursor = Some(Point::new(5, 2));
+    assert_snapshot("minimap_of_a_large_map", &render_in(&mut app, 80, 16));
+}
+
+#[test]
+fn clicking_the_minimap_moves_the_camera() {
+    let mut app = large_world();
+    render_in(&mut app, 80, 16);
+    let area = app
+        .minimap_area
+        .expect("The map is too big to be seen whole");
+
+    let click = MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: area.right() - 1,
+        row: area.bottom() - 1,
+        modifiers: KeyModifiers::NONE,
+    };
+    handle_mouse_events(click, &mut app).unwrap();
+    let cursor = app.cursor.unwrap();
+    assert!(cursor.x > 55 && cursor.y > 15, "{:?}", cursor);
+}
@@ -0,0 +1,131 @@
+//! Carries out commands and records and replays sessions.
+
+use specs::{Join, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::map_generator::MapGenerator;
+use terminal_transport_game::replay::{Command, Recording};
+use terminal_transport_game::sim_events::SimClock;
+
+#[test]
+fn building_track_keeps_the_neighbours_wires_and_height() {
+    let mut app = App::new();
+    let mut map = Map::simple_ring(12, 6);
+    map.set_electrified(Point::new(5, 0), true);
+    map.set_elevation(Point::new(5, 0), 0.3);
+    app.ecs.insert(map);
+
+    assert!(Command::BuildTrack(Point::new(5, 1)).apply(&mut app.ecs));
+
+    let tile = app.ecs.fetch::<Map>().get_tile_at_xy(Point::new(5, 0));
+    assert_eq!(tile.shape(), RailShape::TBottom);
+    assert!(tile.is_electrified());
+    assert_eq!(tile.elevation(), 0.3);
+}
+
+/// Cars and motion of every train, in entity order.
+fn trains(app: &App) -> Vec<(TrainParts, TrainParameters)> {
+    (
+        &app.ecs.read_storage::<TrainParts>(),
+        &app.ecs.read_storage::<TrainParameters>(),
+    )
+        .join()
+        .map(|(parts, params)| (parts.clone(), params.clone()))
+        .collect()
+}
+
+/// Plays a generated map for `ticks` ticks with two trains, switching a junction back and forth
+/// and turning a train around along the way.
+fn play_session(ticks: u64) -> (Recording, Vec<(TrainParts, TrainParameters)>) {
+    let mut app = App::new();
+    let generated = MapGenerator::new(7).generate().unwrap();
+    let track: Vec<Point> = (0..generated.map.h())
+        .flat_map(|y| (0..generated.map.w()).map(move |x| Point::new(x, y)))
+        .filter(|&p| generated.map.get_tile_at_xy(p).shape() != RailShape::Empty)
+        .collect();
+    let junction = track
+        .iter()
+        .copied()
+        .find(|&p| generated.map.get_tile_at_xy(p).shape().is_junction())
+        .expect("Generated maps have junctions");
+    generated.insert_into(&mut app.ecs);
+    app.start_recording();
+
+    let vehicles = vec![
+        Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+        Vehicle::passenger_car(1.5),
+    ];
+    let mut spawned = 0;
+    for (&head, direction) in track.iter().step_by(7).zip(Direction::ALL.iter().cycle()) {
+        let command = Command::SpawnTrain {
+            head,
+            direction: *direction,
+            vehicles: vehicles.clone(),
+        };
+        if app.execute(command) {
+            spawned += 1;
+        }
+        if spawned == 2 {
+            break;
+        }
+    }
+    assert_eq!(spawned, 2);
+    let start = trains(&app);
+
+    for tick in 0..ticks {
+        if tick % 25 == 10 {
+            assert!(app.execute(Command::ToggleSwitch(junction)));
+        }
+        if tick % 25 == 20 {
+            // Only standing trains turn around, which is fine either way
+            let head = trains(&app)[0].0.parts[0].position;
+            app.execute(Command::ReverseTrain(head));
+        }
+        app.tick();
+    }
+    let recording = app.recording.take().unwrap();
+    assert!(recording.inputs.len() > 2);
+    let end = trains(&app);
+    assert!(
+        end.iter().zip(&start).any(|(e, s)| e.0 != s.0),
+        "The trains never moved"
+    );
+    (recording, end)
+}
+
+#[test]
+fn replay_ends_where_the_session_did() {
+    let (recording, live) = play_session(100);
+    let path = std::env::temp_dir().join(format!("ttg-replay-{}.json", std::process::id()));
+    recording.save(&path).unwrap();
+    let loaded = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut app = App::new();
+    app.start_playback(loaded);
+    while !app.playback.as_ref().unwrap().is_finished(&app.ecs) {
+        app.tick();
+    }
+    assert_eq!(app.ecs.fetch::<SimClock>().tick, 100);
+    assert!(trains(&app) == live);
+}
+
+#[test]
+fn seeking_back_and_forth_gets_back_to_the_same_state() {
+    let (recording, live) = play_session(100);
+    let mut app = App::new();
+    app.start_playback(recording);
+
+    app.seek_replay(60);
+    let at_60 = trains(&app);
+    app.seek_replay(-45);
+    assert_eq!(app.ecs.fetch::<SimClock>().tick, 15);
+    app.seek_replay(45);
+    assert!(trains(&app) == at_60);
+    app.seek_replay(1000);
+    assert!(trains(&app) == live);
+}
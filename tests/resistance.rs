@@ -0,0 +1,107 @@
+//! Slows trains down on corners and hills, and lets them roll downhill.
+
+use ratatui::style::Color;
+use specs::WorldExt;
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainColors, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::systems::{APPLYING_TRACTION, DRIVING_TRAIN};
+use terminal_transport_game::train::spawn_train;
+
+/// Velocity after one tick of a train coasting at `velocity` with its cars on `positions`.
+fn coast(map: Map, positions: &[Point], direction: Direction, velocity: f32) -> f32 {
+    let mut app = App::new();
+    app.ecs.insert(map);
+    app.set_system_enabled(DRIVING_TRAIN, false);
+    app.set_system_enabled(APPLYING_TRACTION, false);
+    let train = spawn_train(
+        &mut app.ecs,
+        TrainParts::new(
+            positions,
+            &[
+                Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+                Vehicle::passenger_car(1.5),
+            ],
+        ),
+        direction,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    {
+        let mut parameters = app.ecs.write_storage::<TrainParameters>();
+        let params = parameters.get_mut(train).unwrap();
+        params.throttle = 0.0;
+        params.velocity = velocity;
+    }
+    app.tick();
+    let velocity = app
+        .ecs
+        .read_storage::<TrainParameters>()
+        .get(train)
+        .unwrap()
+        .velocity;
+    velocity
+}
+
+/// A 20x6 ring with the top row from x 2 to 10 rising by `grade` per tile to the right.
+fn hill(grade: f32) -> Map {
+    let mut map = Map::simple_ring(20, 6);
+    for x in 2..=10 {
+        map.set_elevation(Point::new(x, 0), grade * (x - 2) as f32);
+    }
+    map
+}
+
+/// The train on the straight top row heading right, its head at (6, 0).
+fn on_straight() -> [Point; 2] {
+    [Point::new(6, 0), Point::new(5, 0)]
+}
+
+#[test]
+fn corner_slows_a_train_down() {
+    let flat = coast(
+        Map::simple_ring(20, 6),
+        &on_straight(),
+        Direction::Right,
+        0.5,
+    );
+    let corner = coast(
+        Map::simple_ring(20, 6),
+        &[Point::new(0, 0), Point::new(0, 1)],
+        Direction::Right,
+        0.5,
+    );
+    assert!(corner < flat, "{} should be below {}", corner, flat);
+}
+
+#[test]
+fn uphill_slows_a_train_down_and_downhill_speeds_it_up() {
+    let flat = coast(
+        Map::simple_ring(20, 6),
+        &on_straight(),
+        Direction::Right,
+        0.5,
+    );
+    let uphill = coast(hill(0.2), &on_straight(), Direction::Right, 0.5);
+    let downhill = coast(hill(-0.2), &on_straight(), Direction::Right, 0.5);
+    assert!(uphill < flat, "{} should be below {}", uphill, flat);
+    assert!(downhill > flat, "{} should be above {}", downhill, flat);
+}
+
+#[test]
+fn standing_train_rolls_off_downhill_but_not_on_the_flat() {
+    let flat = coast(
+        Map::simple_ring(20, 6),
+        &on_straight(),
+        Direction::Right,
+        0.0,
+    );
+    let downhill = coast(hill(-0.2), &on_straight(), Direction::Right, 0.0);
+    assert_eq!(flat, 0.0);
+    assert!(downhill > 0.0);
+}
@@ -0,0 +1,159 @@
+//! Plans routes for trains across the track.
+
+use std::collections::HashSet;
+
+use ratatui::style::Color;
+use specs::{World, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Route, RouteCost, Traction, TrainColors, TrainParameters, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::routing::{find_route, send_train, update_arrival};
+use terminal_transport_game::routing_system::REPLANNING_DELAY;
+use terminal_transport_game::sim_events::EventBus;
+use terminal_transport_game::systems::MOVING_TRAIN;
+use terminal_transport_game::train::spawn_train;
+
+/// A 12x6 ring with a shortcut straight down from (3, 0) to (3, 5).
+fn ring_with_shortcut() -> Map {
+    let mut map = Map::simple_ring(12, 6);
+    map.put_tile_at_xy(Point::new(3, 0), RailShape::TBottom);
+    for y in 1..5 {
+        map.put_tile_at_xy(Point::new(3, y), RailShape::Vertical);
+    }
+    map.put_tile_at_xy(Point::new(3, 5), RailShape::TTop);
+    map
+}
+
+/// A two-car train on the top row heading right, its head at (2, 0).
+fn train() -> (TrainParts, TrainParameters) {
+    let parts = TrainParts::new(
+        &[Point::new(2, 0), Point::new(1, 0)],
+        &[
+            Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+            Vehicle::passenger_car(1.5),
+        ],
+    );
+    let params = TrainParameters::new(&parts, Direction::Right);
+    (parts, params)
+}
+
+fn route(map: &Map, destination: Point, blocked: &[Point]) -> Option<Vec<Point>> {
+    let (parts, params) = train();
+    let blocked: HashSet<Point> = blocked.iter().copied().collect();
+    find_route(
+        map,
+        &parts,
+        &params,
+        destination,
+        RouteCost::Shortest,
+        &blocked,
+    )
+}
+
+#[test]
+fn route_takes_the_shorter_of_two_ways() {
+    let map = ring_with_shortcut();
+    let path = route(&map, Point::new(6, 5), &[]).unwrap();
+
+    assert_eq!(path.first(), Some(&Point::new(2, 0)));
+    assert_eq!(path.last(), Some(&Point::new(6, 5)));
+    assert_eq!(path.len(), 10);
+    assert!(path.contains(&Point::new(3, 2)));
+}
+
+#[test]
+fn route_goes_around_a_blocked_tile() {
+    let map = ring_with_shortcut();
+    let path = route(&map, Point::new(6, 5), &[Point::new(3, 2)]).unwrap();
+
+    assert_eq!(path.last(), Some(&Point::new(6, 5)));
+    assert_eq!(path.len(), 20);
+    assert!(!path.contains(&Point::new(3, 2)));
+    assert!(path.contains(&Point::new(11, 3)));
+}
+
+#[test]
+fn unreachable_destination_has_no_route() {
+    let map = ring_with_shortcut();
+
+    assert_eq!(route(&map, Point::new(6, 2), &[]), None);
+    // With both ways blocked there is no way through either
+    assert_eq!(
+        route(
+            &map,
+            Point::new(6, 5),
+            &[Point::new(3, 2), Point::new(11, 3)]
+        ),
+        None
+    );
+}
+
+#[test]
+fn arrival_is_announced_once() {
+    let train = World::new().entities().create();
+    let mut route = Route::new(Point::new(6, 5), RouteCost::Shortest);
+    let mut events = EventBus::default();
+
+    assert!(!update_arrival(
+        &mut route,
+        Point::new(5, 5),
+        train,
+        1,
+        &mut events
+    ));
+    assert!(update_arrival(
+        &mut route,
+        Point::new(6, 5),
+        train,
+        2,
+        &mut events
+    ));
+    assert!(!update_arrival(
+        &mut route,
+        Point::new(6, 5),
+        train,
+        3,
+        &mut events
+    ));
+    assert!(route.arrived);
+    assert_eq!(events.len(), 1);
+    let event = events.events().next().unwrap();
+    assert_eq!(event.tick, 2);
+    assert_eq!(event.train, Some(train));
+}
+
+#[test]
+fn unreachable_destination_is_looked_for_again_only_after_a_while() {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(12, 6));
+    app.set_system_enabled(MOVING_TRAIN, false);
+    let (parts, _) = train();
+    let train = spawn_train(
+        &mut app.ecs,
+        parts,
+        Direction::Right,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    let destination = Point::new(3, 2);
+    send_train(&mut app.ecs, train, destination, RouteCost::Shortest);
+    let route = |app: &App| app.ecs.read_storage::<Route>().get(train).unwrap().clone();
+
+    app.tick();
+    assert!(route(&app).path.is_empty());
+    assert_eq!(route(&app).retry_at, REPLANNING_DELAY);
+
+    // The shortcut is laid, but the train only notices once the delay is up
+    app.ecs.insert(ring_with_shortcut());
+    for _ in 1..REPLANNING_DELAY {
+        app.tick();
+        assert!(route(&app).path.is_empty());
+    }
+    app.tick();
+    assert_eq!(route(&app).path.last(), Some(&destination));
+}
@@ -0,0 +1,134 @@
+//! Keeps trains to the speed limits of the track.
+
+use ratatui::style::Color;
+use specs::{Entity, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainColors, TrainParameters, TrainParts, Vehicle,
+    BRAKE_DECELERATION,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::train::spawn_train;
+
+/// A train on the top row of the ring heading right, its cars on `xs` from the head back.
+fn train(app: &mut App, xs: &[i32], velocity: f32) -> Entity {
+    let positions: Vec<Point> = xs.iter().map(|&x| Point::new(x, 0)).collect();
+    let vehicles: Vec<Vehicle> = std::iter::once(Vehicle::locomotive(Traction::Diesel, 2.0, 3.0))
+        .chain(std::iter::repeat(Vehicle::passenger_car(1.5)))
+        .take(xs.len())
+        .collect();
+    let train = spawn_train(
+        &mut app.ecs,
+        TrainParts::new(&positions, &vehicles),
+        Direction::Right,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    );
+    app.ecs
+        .write_storage::<TrainParameters>()
+        .get_mut(train)
+        .unwrap()
+        .velocity = velocity;
+    train
+}
+
+/// A 40x6 ring with a speed limit on the top row tiles in `xs`.
+fn ring(xs: std::ops::Range<i32>, limit: f32) -> App {
+    let mut app = App::new();
+    let mut map = Map::simple_ring(40, 6);
+    for x in xs {
+        map.set_speed_limit(Point::new(x, 0), Some(limit));
+    }
+    app.ecs.insert(map);
+    app
+}
+
+fn state(app: &App, train: Entity) -> (Point, f32, bool) {
+    let parts = app.ecs.read_storage::<TrainParts>();
+    let parameters = app.ecs.read_storage::<TrainParameters>();
+    let params = parameters.get(train).unwrap();
+    (
+        parts.get(train).unwrap().parts[0].position,
+        params.velocity,
+        params.braking,
+    )
+}
+
+#[test]
+fn turns_are_limited_unless_told_otherwise() {
+    let mut map = Map::simple_ring(12, 6);
+    let corner = Point::new(0, 0);
+    let straight = Point::new(5, 0);
+    assert_eq!(map.get_tile_at_xy(corner).speed_limit(), Some(1.0));
+    assert_eq!(map.get_tile_at_xy(straight).speed_limit(), None);
+
+    map.set_speed_limit(corner, Some(0.5));
+    map.set_speed_limit(straight, Some(1.5));
+    assert_eq!(map.get_tile_at_xy(corner).speed_limit(), Some(0.5));
+    assert_eq!(map.get_tile_at_xy(straight).speed_limit(), Some(1.5));
+}
+
+#[test]
+fn train_keeps_to_the_lowest_limit_until_its_tail_is_clear() {
+    let mut app = ring(3..6, 0.3);
+    let train = train(&mut app, &[7, 6, 5], 0.3);
+    {
+        let map = app.ecs.fetch::<Map>();
+        let parts = app.ecs.read_storage::<TrainParts>();
+        let parameters = app.ecs.read_storage::<TrainParameters>();
+        let limit = parameters
+            .get(train)
+            .unwrap()
+            .speed_limit(&map, parts.get(train).unwrap());
+        assert_eq!(limit, 0.3);
+    }
+
+    // Only the last car is still on the slow stretch, yet the whole train keeps to it
+    let mut ticks = 0;
+    loop {
+        let tail_on_limit = app
+            .ecs
+            .read_storage::<TrainParts>()
+            .get(train)
+            .unwrap()
+            .parts[2]
+            .position
+            .x
+            < 6;
+        if !tail_on_limit {
+            break;
+        }
+        app.tick();
+        assert!(state(&app, train).1 <= 0.3);
+        ticks += 1;
+        assert!(ticks < 20, "The train never left the slow stretch");
+    }
+    for _ in 0..5 {
+        app.tick();
+    }
+    assert!(state(&app, train).1 > 0.3);
+}
+
+#[test]
+fn driver_brakes_ahead_of_a_lower_limit() {
+    let mut app = ring(30..34, 0.5);
+    let train = train(&mut app, &[5, 4], 2.0);
+
+    let mut braked_before = false;
+    let mut approach = state(&app, train).1;
+    loop {
+        app.tick();
+        let (head, velocity, braking) = state(&app, train);
+        if head.x >= 30 {
+            break;
+        }
+        braked_before |= braking;
+        approach = velocity;
+    }
+    assert!(braked_before, "The driver didn't brake before the limit");
+    // Slowed down in time, rather than being cut to the limit on the spot
+    assert!(approach <= 0.5 + BRAKE_DECELERATION, "{}", approach);
+}
@@ -0,0 +1,20 @@
+//! Turns the systems of the game on and off.
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::systems::{COLLECTING_METRICS, MOVING_TRAIN};
+
+#[test]
+fn only_registered_systems_can_be_disabled() {
+    let mut app = App::new();
+    assert!(app.system_names().contains(&MOVING_TRAIN));
+
+    assert!(app.set_system_enabled(MOVING_TRAIN, false));
+    assert!(!app.is_system_enabled(MOVING_TRAIN));
+    assert!(app.set_system_enabled(MOVING_TRAIN, true));
+    assert!(app.is_system_enabled(MOVING_TRAIN));
+
+    // A typo is reported instead of quietly doing nothing
+    assert!(!app.set_system_enabled("moving_trains", false));
+    assert!(app.is_system_enabled("moving_trains"));
+    assert!(app.is_system_enabled(COLLECTING_METRICS));
+}
@@ -0,0 +1,72 @@
+//! Loads colour themes and switches between them.
+
+use std::fs;
+
+use ratatui::backend::TestBackend;
+use ratatui::style::Color;
+use ratatui::Terminal;
+use specs::{Builder, WorldExt};
+
+use terminal_transport_game::app::{App, MapOverlay};
+use terminal_transport_game::components::{Direction, Point, TrafficLight};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::theme::Theme;
+use terminal_transport_game::ui;
+
+#[test]
+fn theme_file_fills_in_missing_colours_from_the_default() {
+    let dir = std::env::temp_dir().join(format!("ttg-themes-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("night.json"),
+        r##"{"track": "DarkGray", "signal_green": "#00ff00"}"##,
+    )
+    .unwrap();
+    fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+
+    let themes = Theme::load_dir(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(themes.len(), 1);
+    let night = &themes[0];
+    assert_eq!(night.name, "night");
+    assert_eq!(night.track, Color::DarkGray);
+    assert_eq!(night.signal_green, Color::Rgb(0, 255, 0));
+    assert_eq!(night.signal_red, Theme::default().signal_red);
+}
+
+#[test]
+fn monochrome_signals_differ_by_glyph() {
+    let mut app = App::new();
+    app.ecs.insert(Map::simple_ring(12, 6));
+    for (x, is_green) in [(3, true), (6, false)] {
+        app.ecs
+            .create_entity()
+            .with(TrafficLight { is_green })
+            .with(Point::new(x, 0))
+            .with(Direction::Right)
+            .build();
+    }
+    assert!(app.set_theme("monochrome"));
+
+    let mut terminal = Terminal::new(TestBackend::new(12, 6)).unwrap();
+    terminal
+        .draw(|frame| frame.render_widget(ui::render_map(&app.ecs, MapOverlay::None), frame.size()))
+        .unwrap();
+    let buffer = terminal.backend().buffer();
+    let green = buffer.get(3, 0);
+    let red = buffer.get(6, 0);
+    assert_eq!(green.fg, red.fg);
+    assert_ne!(green.symbol(), red.symbol());
+}
+
+#[test]
+fn cycling_wraps_around_the_themes() {
+    let mut app = App::new();
+    let names: Vec<_> = Theme::builtin().into_iter().map(|t| t.name).collect();
+    for expected in names.iter().skip(1).chain(names.first()) {
+        app.cycle_theme();
+        assert_eq!(&app.ecs.fetch::<Theme>().name, expected);
+    }
+    assert!(!app.set_theme("no such theme"));
+}
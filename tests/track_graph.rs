@@ -0,0 +1,144 @@
+//! Compiles track into a graph and splits it into blocks between signals.
+
+use std::collections::HashSet;
+
+use terminal_transport_game::components::{Direction, Point};
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::track_graph::{NodeKind, TrackGraph};
+
+/// Lays a ring with its corners at (x1, y1) and (x2, y2).
+fn put_ring(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) {
+    for x in x1 + 1..x2 {
+        map.put_tile_at_xy(Point::new(x, y1), RailShape::Horizontal);
+        map.put_tile_at_xy(Point::new(x, y2), RailShape::Horizontal);
+    }
+    for y in y1 + 1..y2 {
+        map.put_tile_at_xy(Point::new(x1, y), RailShape::Vertical);
+        map.put_tile_at_xy(Point::new(x2, y), RailShape::Vertical);
+    }
+    map.put_tile_at_xy(Point::new(x1, y1), RailShape::TurnBottomRight);
+    map.put_tile_at_xy(Point::new(x2, y1), RailShape::TurnBottomLeft);
+    map.put_tile_at_xy(Point::new(x1, y2), RailShape::TurnTopRight);
+    map.put_tile_at_xy(Point::new(x2, y2), RailShape::TurnTopLeft);
+}
+
+/// Two rings crossing each other at (4, 3) and (7, 6), with no other branches.
+fn crossing_rings() -> Map {
+    let mut map = Map::empty(12, 10);
+    put_ring(&mut map, 0, 3, 7, 9);
+    put_ring(&mut map, 4, 0, 11, 6);
+    map.put_tile_at_xy(Point::new(4, 3), RailShape::Cross);
+    map.put_tile_at_xy(Point::new(7, 6), RailShape::Cross);
+    map
+}
+
+fn track_tiles(map: &Map) -> HashSet<Point> {
+    (0..map.h())
+        .flat_map(|y| (0..map.w()).map(move |x| Point::new(x, y)))
+        .filter(|&p| map.get_tile_at_xy(p).shape() != RailShape::Empty)
+        .collect()
+}
+
+fn covered(graph: &TrackGraph) -> HashSet<Point> {
+    graph
+        .nodes()
+        .iter()
+        .map(|n| n.position)
+        .chain(graph.edges().iter().flat_map(|e| e.tiles.iter().copied()))
+        .collect()
+}
+
+#[test]
+fn plain_ring_gets_a_single_loop_node() {
+    let map = Map::simple_ring(12, 6);
+    let graph = TrackGraph::build(&map, &[]);
+
+    assert_eq!(graph.nodes().len(), 1);
+    assert_eq!(graph.nodes()[0].kind, NodeKind::Loop);
+    assert_eq!(graph.edges().len(), 1);
+    let edge = &graph.edges()[0];
+    assert_eq!(edge.to, Some(0));
+    assert_eq!(edge.length(), 32);
+    assert_eq!(covered(&graph), track_tiles(&map));
+}
+
+#[test]
+fn siding_branches_off_at_a_junction() {
+    let mut map = Map::simple_ring(12, 6);
+    map.put_tile_at_xy(Point::new(5, 0), RailShape::TBottom);
+    map.put_tile_at_xy(Point::new(5, 1), RailShape::Vertical);
+    map.put_tile_at_xy(Point::new(5, 2), RailShape::EndTop);
+    let graph = TrackGraph::build(&map, &[]);
+
+    let junction = graph.node_at(Point::new(5, 0)).unwrap();
+    let end = graph.node_at(Point::new(5, 2)).unwrap();
+    assert_eq!(graph.node(junction).kind, NodeKind::Junction);
+    assert_eq!(graph.node(end).kind, NodeKind::End);
+    assert_eq!(graph.nodes().len(), 2);
+
+    // The ring runs from the junction back to itself, the siding down to the buffer stop
+    assert_eq!(graph.edges().len(), 2);
+    let siding = graph.edge(graph.edge_from(junction, Direction::Down).unwrap());
+    assert_eq!(siding.tiles, vec![Point::new(5, 1)]);
+    assert_eq!(siding.to, Some(end));
+    assert_eq!(
+        graph.edge_from(junction, Direction::Left),
+        graph.edge_from(junction, Direction::Right)
+    );
+    assert_eq!(covered(&graph), track_tiles(&map));
+}
+
+#[test]
+fn two_signals_split_a_ring_into_two_blocks() {
+    let map = Map::simple_ring(12, 6);
+    let signals = [
+        (Point::new(3, 0), Direction::Right),
+        (Point::new(8, 0), Direction::Right),
+    ];
+    let graph = TrackGraph::build(&map, &signals);
+
+    assert!(graph.nodes().iter().all(|n| n.kind == NodeKind::Signal));
+    let first = graph.block_from(Point::new(3, 0), Direction::Right);
+    let second = graph.block_from(Point::new(8, 0), Direction::Right);
+    assert_eq!(first, (4..8).map(|x| Point::new(x, 0)).collect::<Vec<_>>());
+    assert_eq!(second.len(), 26);
+    assert_eq!(second.first(), Some(&Point::new(9, 0)));
+    assert_eq!(second.last(), Some(&Point::new(2, 0)));
+
+    let mut all: HashSet<Point> = first.into_iter().chain(second).collect();
+    all.extend(signals.iter().map(|&(p, _)| p));
+    assert_eq!(all, track_tiles(&map));
+}
+
+#[test]
+fn loops_joined_only_by_crossings_are_covered() {
+    let map = crossing_rings();
+    let graph = TrackGraph::build(&map, &[]);
+
+    assert_eq!(graph.nodes().len(), 2);
+    assert!(graph.nodes().iter().all(|n| n.kind == NodeKind::Loop));
+    assert_eq!(covered(&graph), track_tiles(&map));
+}
+
+#[test]
+fn signal_on_a_crossing_only_ends_the_line_it_faces_along() {
+    let map = crossing_rings();
+    let signals = [
+        (Point::new(4, 3), Direction::Right),
+        (Point::new(11, 3), Direction::Down),
+    ];
+    let graph = TrackGraph::build(&map, &signals);
+
+    // The ring the crossing signal faces along is one block up to the signal itself
+    let along = graph.block_from(Point::new(4, 3), Direction::Right);
+    assert_eq!(along.len(), 25);
+    assert!(!along.contains(&Point::new(4, 3)));
+    assert!(along.contains(&Point::new(7, 6)));
+
+    // The other ring runs straight over the crossing without stopping
+    let across = graph.block_from(Point::new(11, 3), Direction::Down);
+    assert_eq!(across.len(), 25);
+    assert!(across.contains(&Point::new(4, 3)));
+    assert!(across.contains(&Point::new(4, 0)));
+    assert!(!across.contains(&Point::new(3, 3)));
+}
@@ -0,0 +1,146 @@
+//! Runs electric and steam trains, which depend on the track for power and water.
+
+use ratatui::style::Color;
+use specs::{Entity, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainColors, TrainParameters, TrainParts, Vehicle,
+    STEAM_WATER_CAPACITY,
+};
+use terminal_transport_game::map::Map;
+use terminal_transport_game::train::spawn_train;
+
+/// A train on the top row of a 20x6 ring heading right, its cars on `xs` from the head back.
+fn train(app: &mut App, locomotive: Vehicle, xs: &[i32]) -> Entity {
+    let positions: Vec<Point> = xs.iter().map(|&x| Point::new(x, 0)).collect();
+    let vehicles: Vec<Vehicle> = std::iter::once(locomotive)
+        .chain(std::iter::repeat(Vehicle::passenger_car(1.5)))
+        .take(xs.len())
+        .collect();
+    spawn_train(
+        &mut app.ecs,
+        TrainParts::new(&positions, &vehicles),
+        Direction::Right,
+        TrainColors {
+            main_color: Color::Blue,
+            head_color: Color::LightBlue,
+        },
+    )
+}
+
+fn ring(map: impl FnOnce(&mut Map)) -> App {
+    let mut app = App::new();
+    let mut ring = Map::simple_ring(20, 6);
+    map(&mut ring);
+    app.ecs.insert(ring);
+    app
+}
+
+fn head(app: &App, train: Entity) -> Point {
+    app.ecs
+        .read_storage::<TrainParts>()
+        .get(train)
+        .unwrap()
+        .parts[0]
+        .position
+}
+
+fn velocity(app: &App, train: Entity) -> f32 {
+    app.ecs
+        .read_storage::<TrainParameters>()
+        .get(train)
+        .unwrap()
+        .velocity
+}
+
+fn water(app: &App, train: Entity) -> f32 {
+    app.ecs
+        .read_storage::<TrainParts>()
+        .get(train)
+        .unwrap()
+        .lowest_water()
+        .unwrap()
+}
+
+#[test]
+fn electric_train_stops_where_the_wires_end() {
+    let mut app = ring(|map| {
+        for x in 0..=10 {
+            map.set_electrified(Point::new(x, 0), true);
+        }
+    });
+    let electric = Vehicle::locomotive(Traction::Electric, 2.0, 3.0);
+    let train = train(&mut app, electric, &[3, 2]);
+
+    {
+        let map = app.ecs.fetch::<Map>();
+        let parts = app.ecs.read_storage::<TrainParts>();
+        let parts = parts.get(train).unwrap();
+        assert!(map.can_train_enter(Point::new(10, 0), Direction::Right, parts));
+        assert!(!map.can_train_enter(Point::new(11, 0), Direction::Right, parts));
+        let diesel = TrainParts::new(
+            &[Point::new(3, 0)],
+            &[Vehicle::locomotive(Traction::Diesel, 2.0, 3.0)],
+        );
+        assert!(map.can_train_enter(Point::new(11, 0), Direction::Right, &diesel));
+    }
+
+    for _ in 0..100 {
+        app.tick();
+        assert!(head(&app, train).x <= 10, "The train left the wires");
+    }
+    assert_eq!(head(&app, train), Point::new(10, 0));
+    assert_eq!(velocity(&app, train), 0.0);
+}
+
+#[test]
+fn steam_locomotive_uses_up_water_while_pulling() {
+    let mut app = ring(|_| {});
+    let train = train(
+        &mut app,
+        Vehicle::locomotive(Traction::steam(), 2.0, 3.0),
+        &[3, 2],
+    );
+
+    let mut last = water(&app, train);
+    for _ in 0..10 {
+        app.tick();
+        let now = water(&app, train);
+        assert!(now < last);
+        last = now;
+    }
+    assert!(velocity(&app, train) > 0.0);
+}
+
+#[test]
+fn steam_train_low_on_water_stops_on_the_supply_and_refills() {
+    let supply = Point::new(12, 0);
+    let mut app = ring(|map| map.set_water_supply(supply, true));
+    let steam = Vehicle::locomotive(Traction::Steam { water: 20.0 }, 2.0, 3.0);
+    let train = train(&mut app, steam, &[3, 2]);
+
+    // The driver brakes to stand with the locomotive on the supply
+    let mut ticks = 0;
+    while velocity(&app, train) > 0.0 || head(&app, train) != supply {
+        app.tick();
+        ticks += 1;
+        assert!(
+            head(&app, train).x <= supply.x,
+            "The train ran past the supply"
+        );
+        assert!(ticks < 100, "The train never stopped at the supply");
+    }
+
+    // It waits there until the tank is full, then sets off again
+    while water(&app, train) < STEAM_WATER_CAPACITY {
+        assert_eq!(head(&app, train), supply);
+        app.tick();
+        ticks += 1;
+        assert!(ticks < 200, "The train never filled up");
+    }
+    for _ in 0..10 {
+        app.tick();
+    }
+    assert_ne!(head(&app, train), supply);
+}
@@ -0,0 +1,178 @@
+//! Property tests running trains over generated maps and checking that they
+//! stay in one piece on the track.
+
+use std::collections::HashMap;
+
+use proptest::prelude::*;
+use ratatui::style::Color;
+use specs::{Entity, Join, WorldExt};
+
+use terminal_transport_game::app::App;
+use terminal_transport_game::components::{
+    Direction, Point, Traction, TrainColors, TrainHead, TrainParts, Vehicle,
+};
+use terminal_transport_game::map::{Map, RailShape};
+use terminal_transport_game::map_generator::MapGenerator;
+use terminal_transport_game::occupancy::Occupancy;
+use terminal_transport_game::train::{heading, place_train, spawn_train};
+
+const TICKS: usize = 60;
+
+fn vehicle(kind: u8) -> Vehicle {
+    match kind % 4 {
+        0 => Vehicle::locomotive(Traction::Diesel, 2.0, 3.0),
+        1 => Vehicle::locomotive(Traction::steam(), 2.5, 4.0),
+        2 => Vehicle::passenger_car(1.5),
+        _ => Vehicle::freight_wagon(3.0),
+    }
+}
+
+/// A generated map with trains put wherever they fit, starting from `spots`
+/// taken as indices into the list of track tiles.
+fn world(seed: u64, width: i32, height: i32, trains: &[(usize, Vec<u8>)]) -> App {
+    let mut app = App::new();
+    let generated = MapGenerator::new(seed)
+        .size(width, height)
+        .generate()
+        .unwrap();
+    let track: Vec<Point> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Point::new(x, y)))
+        .filter(|&p| generated.map.get_tile_at_xy(p).shape() != RailShape::Empty)
+        .collect();
+    generated.insert_into(&mut app.ecs);
+
+    for (spot, kinds) in trains {
+        let head = track[spot % track.len()];
+        // Every train has a locomotive at its head
+        let vehicles: Vec<Vehicle> = std::iter::once(vehicle(0))
+            .chain(kinds.iter().map(|&k| vehicle(k)))
+            .collect();
+        let parts = {
+            let map = app.ecs.fetch::<Map>();
+            let occupancy = app.ecs.fetch::<Occupancy>();
+            map.get_tile_at_xy(head)
+                .shape()
+                .connections()
+                .iter()
+                .filter_map(|&d| place_train(&map, head, d, &vehicles))
+                .find(|parts| parts.positions().all(|p| !occupancy.is_occupied(p)))
+        };
+        let Some(parts) = parts else {
+            continue;
+        };
+        let direction = heading(&parts)
+            .or_else(|| {
+                let map = app.ecs.fetch::<Map>();
+                map.get_tile_at_xy(head)
+                    .shape()
+                    .connections()
+                    .first()
+                    .copied()
+            })
+            .expect("Trains stand on track");
+        spawn_train(
+            &mut app.ecs,
+            parts,
+            direction,
+            TrainColors {
+                main_color: Color::Blue,
+                head_color: Color::LightBlue,
+            },
+        );
+    }
+    app
+}
+
+/// Number of cars of every train.
+fn train_lengths(app: &App) -> HashMap<Entity, usize> {
+    (&app.ecs.entities(), &app.ecs.read_storage::<TrainParts>())
+        .join()
+        .map(|(e, t)| (e, t.parts.len()))
+        .collect()
+}
+
+/// Checks that trains only change length by coupling, a train growing by exactly the cars of
+/// one train that is gone.
+fn check_lengths(
+    before: &HashMap<Entity, usize>,
+    after: &HashMap<Entity, usize>,
+) -> Result<(), TestCaseError> {
+    let mut grown: Vec<usize> = Vec::new();
+    for (e, &length) in after {
+        let previous = before.get(e);
+        prop_assert!(previous.is_some(), "train {:?} appeared", e);
+        let previous = *previous.expect("Checked above");
+        prop_assert!(length >= previous, "train {:?} lost cars", e);
+        if length > previous {
+            grown.push(length - previous);
+        }
+    }
+    let mut gone: Vec<usize> = before
+        .iter()
+        .filter(|(e, _)| !after.contains_key(e))
+        .map(|(_, &length)| length)
+        .collect();
+    grown.sort();
+    gone.sort();
+    prop_assert_eq!(grown, gone, "cars moved between trains");
+    Ok(())
+}
+
+/// Checks every train is a chain of cars on connected track with its head in front.
+fn check_trains(app: &App) -> Result<(), TestCaseError> {
+    let map = app.ecs.fetch::<Map>();
+    let heads = app.ecs.read_storage::<TrainHead>();
+    let trains = app.ecs.read_storage::<TrainParts>();
+    let mut seen: Vec<Point> = Vec::new();
+
+    for (head, parts) in (&heads, &trains).join() {
+        prop_assert_eq!(head.position, parts.parts[0].position);
+
+        for (i, part) in parts.parts.iter().enumerate() {
+            let shape = map.get_tile_at_xy(part.position).shape();
+            prop_assert_ne!(shape, RailShape::Empty, "car on an empty tile");
+            prop_assert!(!seen.contains(&part.position), "two cars on one tile");
+            seen.push(part.position);
+
+            if i == 0 {
+                continue;
+            }
+            let front = parts.parts[i - 1].position;
+            let side = Direction::between(part.position, front);
+            prop_assert!(side.is_some(), "cars {} and {} are apart", i - 1, i);
+            let side = side.expect("Checked above");
+            prop_assert!(shape.connections().contains(&side));
+            prop_assert!(map
+                .get_tile_at_xy(front)
+                .shape()
+                .connections()
+                .contains(&side.opposite()));
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn trains_stay_on_track_and_in_one_piece(
+        seed in any::<u64>(),
+        width in 8..40i32,
+        height in 8..24i32,
+        trains in prop::collection::vec((any::<usize>(), prop::collection::vec(any::<u8>(), 0..5)), 1..6),
+    ) {
+        let mut app = world(seed, width, height, &trains);
+        let mut lengths = train_lengths(&app);
+        check_trains(&app)?;
+
+        for _ in 0..TICKS {
+            app.tick();
+            check_trains(&app)?;
+            // Coupling joins trains, but no train ever gains or loses a car otherwise
+            let next = train_lengths(&app);
+            check_lengths(&lengths, &next)?;
+            lengths = next;
+        }
+    }
+}